@@ -1,15 +1,17 @@
 use clap::{crate_version, load_yaml, App};
-use rusqlite::Connection;
 use std::{process, unreachable};
 
-// By default the program operates on the database `notes.db`
-const DEFAULT_DATABSE: &str = "notes";
-
 #[cfg(target_family = "unix")]
-use rtend::{add, delete, edit, find, list, skim, utils};
+use rtend::{
+    add, agenda, attachment, backup, config, delete, edit, encryption, export, find, journal, link,
+    list, merge, profile, review, skim, trash, undo, utils,
+};
 
 #[cfg(target_family = "windows")]
-use rtend::{add, delete, edit, find, list, utils};
+use rtend::{
+    add, agenda, attachment, backup, config, delete, edit, encryption, export, find, journal, link,
+    list, merge, profile, review, trash, undo, utils,
+};
 
 fn main() {
     #[cfg(target_family = "unix")]
@@ -19,30 +21,78 @@ fn main() {
 
     let matches = App::from_yaml(yml).version(crate_version!()).get_matches();
 
-    // The program would switch to whatever database if user uses the --profile flag
-    // instead of using the default database which is "notes.db"
-    let mut db = format!("{}.db", DEFAULT_DATABSE);
-    if matches.is_present("profile") {
-        db = format!("{}.db", matches.value_of("profile").unwrap());
+    // Works on the config file itself, before the command line overrides get applied
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        config::config(config_matches);
+        return;
     }
 
+    // Flags like --table-preset override what is in the config file
+    config::init(&matches);
+
+    // Profiles are managed without opening any of them
+    if let Some(profile_matches) = matches.subcommand_matches("profile") {
+        profile::profile(profile_matches);
+        return;
+    }
+
+    // The program would switch to whatever database if user uses the --db or --profile flag
+    // instead of using the default database, which is "notes.db" unless set otherwise
+    let db = utils::find_db_path(matches.value_of_os("db"), matches.value_of("profile"));
+
     // First check if the database exists yet, if not then would prompt the user to init it first
-    if let Some(_init_matches) = matches.subcommand_matches("init") {
-        if utils::check_first_time() {
-            utils::create_new_db(true, &db).unwrap();
-        } else if !utils::check_database_exists(&db) {
-            utils::create_new_db(false, &db).unwrap();
-        }
-    } else if !utils::check_database_exists(&db) {
-        eprintln!("database does not exist, please run the subcommand `init`");
+    let mut passphrase = None;
+    if let Some(init_matches) = matches.subcommand_matches("init") {
+        if init_matches.is_present("encrypted") {
+            if db.exists() {
+                eprintln!("database already exists, use the subcommand `rekey` to encrypt it");
+                process::exit(1);
+            }
+            encryption::check_support();
+            passphrase = Some(
+                encryption::get_new_passphrase(matches.value_of("keyfile")).unwrap_or_else(|err| {
+                    eprintln!("Could not read the passphrase, error: {}", err);
+                    process::exit(1);
+                }),
+            );
+        }
+
+        if !db.exists() {
+            utils::create_new_db(&db, passphrase.as_deref()).unwrap();
+        }
+    } else if !db.exists() {
+        eprintln!(
+            "database `{}` does not exist, please run the subcommand `init`",
+            db.display()
+        );
         process::exit(1);
     }
 
-    let conn = Connection::open(&utils::find_data_dir().unwrap().join(&db)).unwrap_or_else(|err| {
+    // Encrypted profiles need their passphrase before anything can be read
+    if passphrase.is_none() && encryption::is_encrypted(&db) {
+        encryption::check_support();
+        let key = encryption::get_passphrase(matches.value_of("keyfile")).unwrap_or_else(|err| {
+            eprintln!("Could not read the passphrase, error: {}", err);
+            process::exit(1);
+        });
+        passphrase = Some(key);
+    }
+
+    let conn = utils::open_db(&db, passphrase.as_deref()).unwrap_or_else(|err| {
         eprintln!("Could not open database! Error: {}", err);
         process::exit(1);
     });
 
+    // Changes are filed in the journal under the command line that made them
+    if let (name, Some(_)) = matches.subcommand() {
+        if journal::JOURNALED_COMMANDS.contains(&name) {
+            journal::begin(&conn).unwrap_or_else(|err| {
+                eprintln!("Could not write to the journal, error: {}", err);
+                process::exit(1);
+            });
+        }
+    }
+
     #[cfg(target_family = "unix")]
     let term_width = utils::get_term_width();
 
@@ -53,6 +103,14 @@ fn main() {
             add::add(add_matches, conn);
         }
 
+        ("backup", Some(backup_matches)) => {
+            backup::backup(backup_matches, conn, passphrase.as_deref());
+        }
+
+        ("check", Some(_check_matches)) => {
+            link::check(conn);
+        }
+
         ("delete", Some(delete_matches)) => {
             delete::delete(delete_matches, conn);
         }
@@ -61,10 +119,22 @@ fn main() {
             edit::edit(edit_matches, conn);
         }
 
+        ("export", Some(export_matches)) => {
+            export::export(export_matches, conn);
+        }
+
+        ("export-attachment", Some(export_matches)) => {
+            attachment::export_attachment(export_matches, conn);
+        }
+
         ("find", Some(find_matches)) => {
             find::find(find_matches, conn);
         }
 
+        ("import", Some(import_matches)) => {
+            export::import(import_matches, conn);
+        }
+
         // It was already hanlded in the above code, it still needs to be here though
         // else the program would panic because of unreachable code
         ("init", Some(_init_matches)) => {}
@@ -73,8 +143,44 @@ fn main() {
             list::list(list_matches, conn);
         }
 
+        ("log", Some(log_matches)) => {
+            journal::log(log_matches, conn);
+        }
+
+        ("merge", Some(merge_matches)) => {
+            merge::merge(merge_matches, conn);
+        }
+
+        ("redo", Some(redo_matches)) => {
+            undo::redo(redo_matches, conn);
+        }
+
+        ("rekey", Some(rekey_matches)) => {
+            encryption::rekey(rekey_matches, conn);
+        }
+
+        ("restore", Some(restore_matches)) => {
+            backup::restore(restore_matches, conn, passphrase.as_deref());
+        }
+
+        ("review", Some(review_matches)) => {
+            review::review(review_matches, conn);
+        }
+
+        ("agenda", Some(agenda_matches)) => {
+            agenda::agenda(agenda_matches, conn);
+        }
+
+        ("trash", Some(trash_matches)) => {
+            trash::trash(trash_matches, conn);
+        }
+
+        ("undo", Some(undo_matches)) => {
+            undo::undo(undo_matches, conn);
+        }
+
         ("skim", Some(skim_matches)) => {
-            skim::skim(skim_matches, term_width, conn);
+            skim::skim(skim_matches, term_width, conn, passphrase.as_deref());
         }
 
         // The program actually never reaches here because of yaml settings
@@ -89,6 +195,14 @@ fn main() {
             add::add(add_matches, conn);
         }
 
+        ("backup", Some(backup_matches)) => {
+            backup::backup(backup_matches, conn, passphrase.as_deref());
+        }
+
+        ("check", Some(_check_matches)) => {
+            link::check(conn);
+        }
+
         ("delete", Some(delete_matches)) => {
             delete::delete(delete_matches, conn);
         }
@@ -97,10 +211,22 @@ fn main() {
             edit::edit(edit_matches, conn);
         }
 
+        ("export", Some(export_matches)) => {
+            export::export(export_matches, conn);
+        }
+
+        ("export-attachment", Some(export_matches)) => {
+            attachment::export_attachment(export_matches, conn);
+        }
+
         ("find", Some(find_matches)) => {
             find::find(find_matches, conn);
         }
 
+        ("import", Some(import_matches)) => {
+            export::import(import_matches, conn);
+        }
+
         // It was already hanlded in the above code, it still needs to be here though
         // else the program would panic because of unreachable code
         ("init", Some(_init_matches)) => {}
@@ -109,6 +235,42 @@ fn main() {
             list::list(list_matches, conn);
         }
 
+        ("log", Some(log_matches)) => {
+            journal::log(log_matches, conn);
+        }
+
+        ("merge", Some(merge_matches)) => {
+            merge::merge(merge_matches, conn);
+        }
+
+        ("redo", Some(redo_matches)) => {
+            undo::redo(redo_matches, conn);
+        }
+
+        ("rekey", Some(rekey_matches)) => {
+            encryption::rekey(rekey_matches, conn);
+        }
+
+        ("restore", Some(restore_matches)) => {
+            backup::restore(restore_matches, conn, passphrase.as_deref());
+        }
+
+        ("review", Some(review_matches)) => {
+            review::review(review_matches, conn);
+        }
+
+        ("agenda", Some(agenda_matches)) => {
+            agenda::agenda(agenda_matches, conn);
+        }
+
+        ("trash", Some(trash_matches)) => {
+            trash::trash(trash_matches, conn);
+        }
+
+        ("undo", Some(undo_matches)) => {
+            undo::undo(undo_matches, conn);
+        }
+
         // The program actually never reaches here because of yaml settings
         ("", None) => println!("Run the program with --help to get started"),
         _ => unreachable!(),
@@ -1,14 +1,66 @@
 use atty::{is, Stream};
 use clap::ArgMatches;
-use rusqlite::{self, params, Connection, NO_PARAMS};
-use std::io::{self, Read};
-use std::{process, str::FromStr, unreachable};
+use rusqlite::{self, params, Connection, OptionalExtension, NO_PARAMS};
+use std::io::{self, Read, Write};
+use std::{path, process, str::FromStr, unreachable};
 
+use crate::agenda;
+use crate::attachment;
+use crate::item;
+use crate::item::ComfyTable;
+use crate::link;
+use crate::property::{self, PropertyType};
+use crate::revision::SnippetKind;
+use crate::tag::TagKind;
 use crate::utils;
 
 pub fn add(args: &ArgMatches, conn: Connection) {
+    if args.is_present("due")
+        && !(args.is_present("add_snippet") || args.is_present("add_relation_snippet"))
+    {
+        eprintln!("--due can only be used with --snippet or --relation-snippet");
+        process::exit(1);
+    }
+    let due = args
+        .value_of("due")
+        .and_then(|due| agenda::get_due(&conn, due));
+
     if args.is_present("add_entity") {
         let name = args.value_of("add_entity").unwrap();
+
+        if !args.is_present("force") {
+            let similar_aliases = find_similar_aliases(&conn, name).unwrap_or_else(|e| {
+                eprintln!("Could not check for duplicate aliases, error: {}", e);
+                process::exit(1);
+            });
+
+            if !similar_aliases.is_empty() {
+                let mut entity_ids: Vec<u32> = similar_aliases
+                    .iter()
+                    .map(|alias| alias.entity_id)
+                    .collect();
+                entity_ids.sort_unstable();
+                entity_ids.dedup();
+
+                println!("Found existing aliases like `{}`", name);
+                let cmfs = item::ComfyStruct {
+                    data: similar_aliases,
+                };
+                cmfs.print_comfy_table();
+
+                if let Some(entity_id) = get_duplicate_choice(&entity_ids) {
+                    match add_alias_to_entity(conn, entity_id, name) {
+                        Ok(()) => println!("alias `{}` to entity_id `{}` added", name, entity_id),
+                        Err(e) => {
+                            eprintln!("Could not add alias to entity, error: {}", e);
+                            process::exit(1);
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+
         match add_new_entity(conn, name) {
             Ok(()) => (),
             Err(e) => {
@@ -46,7 +98,13 @@ pub fn add(args: &ArgMatches, conn: Connection) {
             process::exit(1);
         });
 
-        match add_relation_two_entities(conn, entity_id_a, entity_id_b) {
+        match add_relation_two_entities(
+            conn,
+            entity_id_a,
+            entity_id_b,
+            args.value_of("label"),
+            args.value_of("inverse_label"),
+        ) {
             Ok(()) => (),
             Err(e) => {
                 eprintln!("Could not add relation between two entities, error: {}", e);
@@ -59,7 +117,7 @@ pub fn add(args: &ArgMatches, conn: Connection) {
                 eprintln!("entity_id must be an u32");
                 process::exit(1);
             });
-        match add_new_snippet(conn, entity_id) {
+        match add_new_snippet(conn, entity_id, due) {
             Ok(()) => {
                 println!("{}", "-".repeat(40));
                 println!("new data snippet added to entity id `{}`", entity_id);
@@ -75,7 +133,7 @@ pub fn add(args: &ArgMatches, conn: Connection) {
                 eprintln!("relation_id must be an u32");
                 process::exit(1);
             });
-        match add_relation_snippet(conn, relation_id) {
+        match add_relation_snippet(conn, relation_id, due) {
             Ok(()) => {
                 println!("{}", "-".repeat(40));
                 println!("new data snippet added to relation id `{}`", relation_id);
@@ -85,6 +143,61 @@ pub fn add(args: &ArgMatches, conn: Connection) {
                 process::exit(1);
             }
         }
+    } else if args.is_present("add_tag") {
+        let tag_args: Vec<_> = args.values_of("add_tag").unwrap().collect();
+        let kind = TagKind::from_str(tag_args[0]).unwrap_or_else(|_err| {
+            eprintln!("kind must be either `entity` or `snippet`");
+            process::exit(1);
+        });
+        let id = u32::from_str(tag_args[1]).unwrap_or_else(|_err| {
+            eprintln!("{}_id must be an u32", kind);
+            process::exit(1);
+        });
+
+        match add_tag(conn, kind, id, tag_args[2]) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not add tag, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("add_property") {
+        let property_args: Vec<_> = args.values_of("add_property").unwrap().collect();
+        let entity_id = u32::from_str(property_args[0]).unwrap_or_else(|_err| {
+            eprintln!("entity_id must be an u32");
+            process::exit(1);
+        });
+        let key = property_args[1];
+        if let Err(e) = property::check_key(key) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        let kind = args
+            .value_of("property_type")
+            .map(|kind| PropertyType::from_str(kind).unwrap());
+        let (kind, value) = property::get_value(property_args[2], kind);
+
+        match property::add_property(conn, entity_id, key, kind, value) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not add property to entity, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("add_attachment") {
+        let attach_args: Vec<_> = args.values_of("add_attachment").unwrap().collect();
+        let entity_id = u32::from_str(attach_args[0]).unwrap_or_else(|_err| {
+            eprintln!("entity_id must be an u32");
+            process::exit(1);
+        });
+
+        match attachment::add_attachment(conn, entity_id, path::Path::new(attach_args[1])) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not attach file to entity, error: {}", e);
+                process::exit(1);
+            }
+        }
     }
 }
 
@@ -98,6 +211,83 @@ fn add_alias_to_entity(conn: Connection, entity_id: u32, name: &str) -> rusqlite
     Ok(())
 }
 
+// Names are compared case-folded and with their whitespace collapsed
+fn fold_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn find_similar_aliases(
+    conn: &Connection,
+    name: &str,
+) -> rusqlite::Result<Vec<item::EntityFoundLong>> {
+    let mut stmt = conn.prepare(
+        "SELECT a.id, a.name, a.entity_id, (SELECT group_concat(b.name, '; ') from alias b
+        where a.entity_id = b.entity_id and a.id != b.id and b.trash_id is null) as other_alias,
+        a.updated from alias a where a.trash_id is null order by a.name",
+    )?;
+
+    let alias_iter = stmt.query_map(NO_PARAMS, |row| {
+        Ok(item::EntityFoundLong {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            entity_id: row.get(2)?,
+            other_alias: row.get(3).unwrap_or_else(|_| "".to_string()),
+            updated: row.get(4)?,
+        })
+    })?;
+
+    // SQLite only folds ASCII, so the comparison happens here
+    let folded_name = fold_name(name);
+    let mut tmp_vec = Vec::new();
+    for alias in alias_iter {
+        let alias = alias?;
+        if fold_name(&alias.name) == folded_name {
+            tmp_vec.push(alias);
+        }
+    }
+
+    Ok(tmp_vec)
+}
+
+// Returns the entity the name should be added to as an alias, or None for a new entity
+fn get_duplicate_choice(entity_ids: &[u32]) -> Option<u32> {
+    if entity_ids.len() == 1 {
+        print!(
+            "Add as a [n]ew entity or as an [a]lias to entity_id {}? ",
+            entity_ids[0]
+        );
+    } else {
+        let id_list: Vec<String> = entity_ids.iter().map(|id| id.to_string()).collect();
+        print!(
+            "Add as a [n]ew entity or as an alias to entity_id [{}]? ",
+            id_list.join("/")
+        );
+    }
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(_) => (),
+        Err(e) => eprintln!("Could not read stdin, error: {}", e),
+    }
+    let input = input.trim();
+
+    if input == "n" || input == "new" {
+        None
+    } else if let Some(entity_id) = entity_ids
+        .iter()
+        .find(|id| id.to_string() == input || (entity_ids.len() == 1 && input == "a"))
+    {
+        Some(*entity_id)
+    } else {
+        println!("Aborted");
+        process::exit(1);
+    }
+}
+
 fn get_latest_entity_id(conn: Connection) -> rusqlite::Result<u32> {
     // Can also do "SELECT id from entity order by id limit 1" as an alternative, the below is
     // probably faster though, idk, probably 0 performance gain
@@ -134,11 +324,84 @@ fn get_latest_relation_id(conn: Connection) -> rusqlite::Result<u32> {
     )
 }
 
-fn add_relation_two_entities(conn: Connection, id_a: u32, id_b: u32) -> rusqlite::Result<()> {
+// Looks the relation type up by its label and adds it if there is none. An inverse label is
+// only filled in when the type has none yet, it is never changed since that would change how
+// every relation of the type reads. The inverse label it has is returned if it is a different one
+pub fn find_or_add_relation_type(
+    conn: &Connection,
+    label: &str,
+    inverse_label: Option<&str>,
+) -> rusqlite::Result<(u32, Option<String>)> {
+    let existing: Option<(u32, Option<String>)> = conn
+        .query_row_and_then(
+            "SELECT id, inverse_label from relation_type where label = (?)",
+            params![label],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    match (existing, inverse_label) {
+        (Some((id, Some(stored))), Some(inverse_label)) if stored != inverse_label => {
+            Ok((id, Some(stored)))
+        }
+        (Some((id, None)), Some(inverse_label)) => {
+            conn.execute(
+                "UPDATE relation_type set inverse_label = (?1) where id = (?2)",
+                params![inverse_label, id],
+            )?;
+            Ok((id, None))
+        }
+        (Some((id, _)), _) => Ok((id, None)),
+        (None, _) => {
+            conn.execute(
+                "INSERT INTO relation_type (label, inverse_label) VALUES (?1, ?2)",
+                params![label, inverse_label],
+            )?;
+            Ok((conn.last_insert_rowid() as u32, None))
+        }
+    }
+}
+
+fn add_relation_two_entities(
+    conn: Connection,
+    mut id_a: u32,
+    mut id_b: u32,
+    label: Option<&str>,
+    inverse_label: Option<&str>,
+) -> rusqlite::Result<()> {
+    let mut relation_type_id = None;
+    if let Some(label) = label {
+        // `b <label> a` is stored as `a <inverse_label> b` when the label is a known inverse label
+        let inverse_of: Option<u32> = conn
+            .query_row_and_then(
+                "SELECT id from relation_type where inverse_label = (?)",
+                params![label],
+                |id| id.get(0),
+            )
+            .optional()?;
+
+        match inverse_of {
+            Some(id) if inverse_label.is_none() => {
+                std::mem::swap(&mut id_a, &mut id_b);
+                relation_type_id = Some(id);
+            }
+            _ => match find_or_add_relation_type(&conn, label, inverse_label)? {
+                (id, None) => relation_type_id = Some(id),
+                (_, Some(stored)) => {
+                    println!(
+                        "relation label `{}` already has the inverse label `{}`. Nothing got added!",
+                        label, stored
+                    );
+                    return Ok(());
+                }
+            },
+        }
+    }
+
     let rows_returned = conn.execute(
-        "INSERT INTO relation (entity_id_a, entity_id_b) VALUES
-                 (?1, ?2)",
-        params![id_a, id_b],
+        "INSERT INTO relation (entity_id_a, entity_id_b, relation_type_id) VALUES
+                 (?1, ?2, ?3)",
+        params![id_a, id_b, relation_type_id],
     )?;
 
     match rows_returned {
@@ -156,7 +419,7 @@ fn add_relation_two_entities(conn: Connection, id_a: u32, id_b: u32) -> rusqlite
     Ok(())
 }
 
-fn add_new_snippet(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
+fn add_new_snippet(conn: Connection, entity_id: u32, due: Option<String>) -> rusqlite::Result<()> {
     // Check if Stdin pipe is open, if it is then these messages will be omitted
     if is(Stream::Stdin) {
         if cfg!(taget_os = "windows") {
@@ -179,14 +442,24 @@ fn add_new_snippet(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
     }
 
     conn.execute(
-        "INSERT INTO snippet (data, entity_id) VALUES (?1, ?2)",
-        params![data, entity_id],
+        "INSERT INTO snippet (data, entity_id, due) VALUES (?1, ?2, ?3)",
+        params![data, entity_id, due],
+    )?;
+    link::update_links(
+        &conn,
+        SnippetKind::Snippet,
+        conn.last_insert_rowid() as u32,
+        &data,
     )?;
 
     Ok(())
 }
 
-fn add_relation_snippet(conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
+fn add_relation_snippet(
+    conn: Connection,
+    relation_id: u32,
+    due: Option<String>,
+) -> rusqlite::Result<()> {
     // Check if Stdin pipe is open, if it is then these messages will be omitted
     if is(Stream::Stdin) {
         if cfg!(taget_os = "windows") {
@@ -209,9 +482,42 @@ fn add_relation_snippet(conn: Connection, relation_id: u32) -> rusqlite::Result<
     }
 
     conn.execute(
-        "INSERT INTO relation_snippet (data, relation_id) VALUES (?1, ?2)",
-        params![data, relation_id],
+        "INSERT INTO relation_snippet (data, relation_id, due) VALUES (?1, ?2, ?3)",
+        params![data, relation_id, due],
     )?;
+    link::update_links(
+        &conn,
+        SnippetKind::RelationSnippet,
+        conn.last_insert_rowid() as u32,
+        &data,
+    )?;
+
+    Ok(())
+}
+
+fn add_tag(mut conn: Connection, kind: TagKind, id: u32, name: &str) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute("INSERT OR IGNORE INTO tag (name) VALUES (?)", params![name])?;
+
+    let rows_returned = match kind {
+        TagKind::Entity => tx.execute(
+            "INSERT OR IGNORE INTO entity_tag (entity_id, tag_id) VALUES
+                 (?1, (SELECT id from tag where name = ?2))",
+            params![id, name],
+        )?,
+        TagKind::Snippet => tx.execute(
+            "INSERT OR IGNORE INTO snippet_tag (snippet_id, tag_id) VALUES
+                 (?1, (SELECT id from tag where name = ?2))",
+            params![id, name],
+        )?,
+    };
+    tx.commit()?;
+
+    match rows_returned {
+        0 => println!("{} id `{}` is already tagged `{}`", kind, id, name),
+        1 => println!("tag `{}` added to {} id `{}`", name, kind, id),
+        _ => unreachable!(),
+    }
 
     Ok(())
 }
@@ -0,0 +1,264 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, Connection, NO_PARAMS};
+use std::{process, str::FromStr};
+use time::Date;
+
+use crate::config;
+use crate::item;
+use crate::item::ComfyTable;
+use crate::revision::SnippetKind;
+
+const WEEKDAYS: &[&str] = &[
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+
+pub fn agenda(args: &ArgMatches, conn: Connection) {
+    let days = u32::from_str(args.value_of("days").unwrap()).unwrap_or_else(|_err| {
+        eprintln!("days must be an u32");
+        process::exit(1);
+    });
+
+    match list_agenda(conn, days) {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("Could not list agenda, error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// SQLite date modifiers relative to today, for the words a due date can be given as
+fn due_modifiers(due: &str) -> Option<Vec<String>> {
+    let due = due.trim().to_lowercase();
+    if due == "today" {
+        return Some(Vec::new());
+    } else if due == "tomorrow" {
+        return Some(vec!["+1 days".to_string()]);
+    } else if let Some(weekday) = WEEKDAYS
+        .iter()
+        .position(|name| *name == due || name[..3] == due)
+    {
+        // The next one, or today if it is that day already
+        return Some(vec![format!("weekday {}", weekday)]);
+    }
+
+    // +3d, +2w or +1m
+    let amount = due.strip_prefix('+')?;
+    let unit = amount.chars().last()?;
+    let count = u32::from_str(&amount[..amount.len() - unit.len_utf8()]).ok()?;
+    match unit {
+        'd' => Some(vec![format!("+{} days", count)]),
+        'w' => Some(vec![format!("+{} days", count.checked_mul(7)?)]),
+        'm' => Some(vec![format!("+{} months", count)]),
+        _ => None,
+    }
+}
+
+// Returns the due date as YYYY-MM-DD, or None if it can't be read
+pub fn parse_due(conn: &Connection, due: &str) -> rusqlite::Result<Option<String>> {
+    if let Ok(date) = Date::parse(due.trim(), "%Y-%m-%d") {
+        return Ok(Some(date.format("%Y-%m-%d")));
+    }
+
+    match due_modifiers(due) {
+        Some(modifiers) => {
+            let mut date: String =
+                conn.query_row_and_then("SELECT date('now', 'localtime')", NO_PARAMS, |row| {
+                    row.get(0)
+                })?;
+            for modifier in modifiers {
+                date = conn.query_row_and_then(
+                    "SELECT date((?1), (?2))",
+                    params![date, modifier],
+                    |row| row.get(0),
+                )?;
+            }
+            Ok(Some(date))
+        }
+        None => Ok(None),
+    }
+}
+
+// Reads --due or exits, `none` clears the due date
+pub fn get_due(conn: &Connection, due: &str) -> Option<String> {
+    if due == "none" {
+        return None;
+    }
+
+    match parse_due(conn, due) {
+        Ok(Some(date)) => Some(date),
+        Ok(None) => {
+            eprintln!(
+                "due date must be YYYY-MM-DD, today, tomorrow, a weekday or +<n>d/w/m like +3d"
+            );
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Could not read the due date, error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+pub fn set_due(
+    conn: &Connection,
+    kind: SnippetKind,
+    id: u32,
+    due: Option<&str>,
+) -> rusqlite::Result<()> {
+    let table = kind.table();
+    let rows_returned = conn.execute(
+        &format!(
+            "UPDATE {} set due = (?1) where id = (?2) and trash_id is null",
+            table
+        ),
+        params![due, id],
+    )?;
+
+    match (rows_returned, due) {
+        (0, _) => println!(
+            "{} id {} does not exist. Nothing got changed!",
+            table.replace('_', " "),
+            id
+        ),
+        (1, Some(due)) => println!("{} id `{}` is due on {}", table.replace('_', " "), id, due),
+        (1, None) => println!("{} id `{}` is no longer due", table.replace('_', " "), id),
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn day_heading(due: &str, weekday: usize, today: &str) -> String {
+    let name = WEEKDAYS[weekday];
+    let label = format!("{}{} {}", name[..1].to_uppercase(), &name[1..3], due);
+    if due < today {
+        format!("{} (overdue)", label)
+    } else if due == today {
+        format!("{} (today)", label)
+    } else {
+        label
+    }
+}
+
+// Everything due up to `days` days from now, overdue things included
+fn list_agenda(conn: Connection, days: u32) -> rusqlite::Result<()> {
+    let today: String =
+        conn.query_row_and_then("SELECT date('now', 'localtime')", NO_PARAMS, |row| {
+            row.get(0)
+        })?;
+
+    let mut stmt = conn.prepare(
+        "SELECT 's' as type, id, data, cast(entity_id as text),
+        (SELECT group_concat(name, '; ') from alias where alias.entity_id = snippet.entity_id
+        and trash_id is null) as alias_list, due
+        from snippet where due is not null and trash_id is null
+        and due <= date((?1), '+' || (?2) || ' days')
+        UNION ALL
+        SELECT 'rs', relation_snippet.id, data, entity_id_a || ' | ' || entity_id_b,
+        coalesce((SELECT group_concat(name, '; ') from alias where alias.entity_id = entity_id_a
+        and trash_id is null), '') || ' | ' ||
+        coalesce((SELECT group_concat(name, '; ') from alias where alias.entity_id = entity_id_b
+        and trash_id is null), ''), due
+        from relation_snippet join relation on relation.id = relation_snippet.relation_id
+        where due is not null and relation_snippet.trash_id is null
+        and due <= date((?1), '+' || (?2) || ' days')
+        order by 6, 1, 2",
+    )?;
+
+    let agenda_iter = stmt.query_map(params![today, days], |row| {
+        Ok(item::AgendaItem {
+            data_type: row.get(0)?,
+            id: row.get(1)?,
+            data: row.get(2)?,
+            entity_ids: row.get(3)?,
+            alias_list: row.get(4).unwrap_or_else(|_| "".to_string()),
+            due: row.get(5)?,
+        })
+    })?;
+
+    // Machine-readable output has the due date in every row instead of headings
+    if !config::output_format().draws_tables() {
+        let cmfs = item::ComfyStruct {
+            data: agenda_iter.collect::<rusqlite::Result<Vec<_>>>()?,
+        };
+        cmfs.print_comfy_table();
+        return Ok(());
+    }
+
+    // Consecutive rows with the same due date make up a day
+    let mut days_due: Vec<(String, Vec<item::AgendaItem>)> = Vec::new();
+    for agenda_item in agenda_iter {
+        let agenda_item = agenda_item?;
+        match days_due.last_mut() {
+            Some((due, items)) if *due == agenda_item.due => items.push(agenda_item),
+            _ => days_due.push((agenda_item.due.clone(), vec![agenda_item])),
+        }
+    }
+
+    if days_due.is_empty() {
+        println!("Nothing is due in the next {} days.", days);
+        return Ok(());
+    }
+
+    for (due, items) in days_due {
+        let weekday: usize = conn.query_row_and_then(
+            "SELECT cast(strftime('%w', (?)) as integer)",
+            params![due],
+            |row| row.get::<_, i64>(0),
+        )? as usize;
+        println!("{}", day_heading(&due, weekday, &today));
+        let cmfs = item::ComfyStruct { data: items };
+        cmfs.print_comfy_table();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::due_modifiers;
+
+    #[test]
+    fn words_are_read_as_modifiers() {
+        assert_eq!(due_modifiers("today"), Some(vec![]));
+        assert_eq!(
+            due_modifiers(" Tomorrow "),
+            Some(vec!["+1 days".to_string()])
+        );
+        assert_eq!(due_modifiers("fri"), Some(vec!["weekday 5".to_string()]));
+        assert_eq!(due_modifiers("sunday"), Some(vec!["weekday 0".to_string()]));
+    }
+
+    #[test]
+    fn offsets_are_read_as_modifiers() {
+        assert_eq!(due_modifiers("+3d"), Some(vec!["+3 days".to_string()]));
+        assert_eq!(due_modifiers("+2w"), Some(vec!["+14 days".to_string()]));
+        assert_eq!(due_modifiers("+1M"), Some(vec!["+1 months".to_string()]));
+    }
+
+    #[test]
+    fn anything_else_is_rejected() {
+        for due in &[
+            "",
+            "+",
+            "+d",
+            "3d",
+            "+3",
+            "+3x",
+            "+-3d",
+            "+3é",
+            "+é",
+            "fr",
+            "+4294967295w",
+        ] {
+            assert_eq!(due_modifiers(due), None, "{}", due);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::{fs, io, path, process, str::FromStr};
+
+use crate::utils::{self, StoreError};
+
+pub fn export_attachment(args: &ArgMatches, conn: Connection) {
+    let attachment_id =
+        u32::from_str(args.value_of("attachment_id").unwrap()).unwrap_or_else(|_err| {
+            eprintln!("attachment_id must be an u32");
+            process::exit(1);
+        });
+
+    match export(
+        conn,
+        attachment_id,
+        path::Path::new(args.value_of("dest").unwrap()),
+    ) {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("Could not export attachment, error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// Every database keeps its files in a directory named after its file, backups included
+pub fn store_dir(db_path: &path::Path) -> path::PathBuf {
+    let mut dir = db_path.to_path_buf().into_os_string();
+    dir.push("-attachments");
+
+    path::PathBuf::from(dir)
+}
+
+fn find_attachment_dir(conn: &Connection) -> rusqlite::Result<path::PathBuf> {
+    Ok(store_dir(&utils::get_db_path(conn)?))
+}
+
+// Copies the stored files of one database to the store of another. Stored files never change,
+// so a hard link does if both are on the same file system
+pub fn copy_store(src_db: &path::Path, dest_db: &path::Path) -> io::Result<()> {
+    let (src_dir, dest_dir) = (store_dir(src_db), store_dir(dest_db));
+    if !src_dir.is_dir() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(&dest_dir)?;
+    for entry in fs::read_dir(&src_dir)? {
+        let entry = entry?;
+        let dest = dest_dir.join(entry.file_name());
+        if !matches!(entry.file_name().to_str(), Some(name) if is_hash(name))
+            || dest.exists()
+            || !entry.file_type()?.is_file()
+        {
+            continue;
+        }
+        if fs::hard_link(entry.path(), &dest).is_err() {
+            fs::copy(entry.path(), &dest)?;
+        }
+    }
+
+    Ok(())
+}
+
+// How many attachments have no stored file
+pub fn count_missing_files(conn: &Connection) -> rusqlite::Result<usize> {
+    let attachment_dir = find_attachment_dir(conn)?;
+    let mut stmt = conn.prepare("SELECT DISTINCT hash from attachment")?;
+    let hashes = stmt
+        .query_map(params![], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(hashes
+        .iter()
+        .filter(|hash| !attachment_dir.join(hash).exists())
+        .count())
+}
+
+// Whether the file is still there, emptying the trash removes the ones nothing refers to
+pub fn is_stored(conn: &Connection, hash: &str) -> rusqlite::Result<bool> {
+    Ok(find_attachment_dir(conn)?.join(hash).exists())
+}
+
+fn hash_of(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// Stored files are named after the SHA-256 of their contents in lowercase hex
+fn is_hash(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+pub fn add_attachment(
+    conn: Connection,
+    entity_id: u32,
+    file_path: &path::Path,
+) -> Result<(), StoreError> {
+    let data = fs::read(file_path)?;
+    let hash = hash_of(&data);
+    let name = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| hash.clone());
+    let mime_type = mime_guess::from_path(file_path)
+        .first_or_octet_stream()
+        .to_string();
+
+    // The same contents are only stored once no matter how many times they get attached
+    let attachment_dir = find_attachment_dir(&conn)?;
+    let stored_path = attachment_dir.join(&hash);
+    let newly_stored = !stored_path.exists();
+    if newly_stored {
+        fs::create_dir_all(&attachment_dir)?;
+        let tmp_path = attachment_dir.join(format!("{}.tmp", hash));
+        fs::write(&tmp_path, &data)?;
+        fs::rename(&tmp_path, &stored_path)?;
+    }
+
+    let inserted = conn.execute(
+        "INSERT INTO attachment (entity_id, name, mime_type, size, hash) VALUES
+                 (?1, ?2, ?3, ?4, ?5)",
+        params![entity_id, name, mime_type, data.len() as i64, hash],
+    );
+    if let Err(e) = inserted {
+        // Only the file stored just now goes again, unless another attachment got it meanwhile
+        let referenced: u32 = conn.query_row_and_then(
+            "SELECT count(*) from attachment where hash = (?)",
+            params![hash],
+            |row| row.get(0),
+        )?;
+        if newly_stored && referenced == 0 {
+            fs::remove_file(&stored_path)?;
+        }
+        return Err(e.into());
+    }
+
+    println!(
+        "file `{}` ({}, {} bytes) attached to entity_id `{}`, their attachment_id is `{}`",
+        name,
+        mime_type,
+        data.len(),
+        entity_id,
+        conn.last_insert_rowid()
+    );
+
+    Ok(())
+}
+
+fn export(conn: Connection, attachment_id: u32, dest: &path::Path) -> Result<(), StoreError> {
+    let attachment: Option<(String, String)> = conn
+        .query_row(
+            "SELECT name, hash from attachment where id = (?) and trash_id is null",
+            params![attachment_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    let (name, hash) = match attachment {
+        Some(attachment) => attachment,
+        None => {
+            println!(
+                "attachment id {} does not exist. Nothing got exported!",
+                attachment_id
+            );
+            return Ok(());
+        }
+    };
+
+    let dest = if dest.is_dir() {
+        dest.join(&name)
+    } else {
+        dest.to_path_buf()
+    };
+    if dest.exists() {
+        println!("{} already exists. Nothing got exported!", dest.display());
+        return Ok(());
+    }
+
+    let data = fs::read(find_attachment_dir(&conn)?.join(&hash))?;
+    if hash_of(&data) != hash {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "stored file of attachment id {} is corrupted",
+                attachment_id
+            ),
+        )
+        .into());
+    }
+    fs::write(&dest, &data)?;
+
+    println!(
+        "attachment id `{}` exported to {}",
+        attachment_id,
+        dest.display()
+    );
+
+    Ok(())
+}
+
+// Removes stored files that no attachment refers to anymore, returns how many were removed
+pub fn remove_unreferenced_files(conn: &Connection) -> Result<usize, StoreError> {
+    let attachment_dir = find_attachment_dir(conn)?;
+    if !attachment_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut stmt = conn.prepare("SELECT 1 from attachment where hash = (?) limit 1")?;
+    let mut removed = 0;
+    for entry in fs::read_dir(&attachment_dir)? {
+        let entry = entry?;
+        // Files being stored by another process end in `.tmp`, anything else isn't rtend's
+        let hash = match entry.file_name().to_str() {
+            Some(name) if is_hash(name) => name.to_string(),
+            _ => continue,
+        };
+        if !stmt.exists(params![hash])? {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
@@ -0,0 +1,329 @@
+use clap::ArgMatches;
+use rusqlite::{self, backup::Backup, Connection, OpenFlags, NO_PARAMS};
+use sha2::{Digest, Sha256};
+use std::{ffi::OsStr, fs, io, path, process, str::FromStr, time::Duration};
+use time::OffsetDateTime;
+
+use crate::attachment;
+use crate::encryption;
+use crate::migration;
+use crate::utils::{self, StoreError};
+
+// How many rotating backups are kept per profile unless --keep says otherwise
+const DEFAULT_KEEP: usize = 7;
+
+// Safety copies made by `restore` go into this directory of the backups, which pruning skips
+const REPLACED_DIR: &str = "replaced";
+
+// Tables every profile has had since the first schema version
+const REQUIRED_TABLES: &[&str] = &["entity", "alias", "snippet", "relation", "relation_snippet"];
+
+// `passphrase` is the one the profile was opened with, None when it is not encrypted
+pub fn backup(args: &ArgMatches, conn: Connection, passphrase: Option<&str>) {
+    if args.is_present("rotate") {
+        let keep = args.value_of("keep").map_or(DEFAULT_KEEP, |keep| {
+            usize::from_str(keep).unwrap_or_else(|_err| {
+                eprintln!("count must be an usize");
+                process::exit(1);
+            })
+        });
+
+        let backup_dir = find_backup_dir(&conn);
+        let dest = backup_dir.join(format!("{}.db", timestamp()));
+        match backup_to(&conn, &dest, passphrase) {
+            Ok(()) => println!("backup written to {}", dest.display()),
+            Err(e) => {
+                eprintln!("Could not back up database, error: {}", e);
+                process::exit(1);
+            }
+        }
+
+        match prune_backups(&backup_dir, keep) {
+            Ok(0) => (),
+            Ok(removed) => println!("{} old backups removed", removed),
+            Err(e) => {
+                eprintln!("Could not remove old backups, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        let mut dest = path::PathBuf::from(args.value_of("dest").unwrap());
+        if dest.is_dir() {
+            let db_path = utils::get_db_path(&conn).unwrap_or_else(|err| {
+                eprintln!("Could not find the database file, error: {}", err);
+                process::exit(1);
+            });
+            dest = dest.join(format!("{}-{}.db", profile_name(&db_path), timestamp()));
+        }
+        if dest.exists() {
+            println!("{} already exists. Nothing got backed up!", dest.display());
+            return;
+        }
+
+        match backup_to(&conn, &dest, passphrase) {
+            Ok(()) => println!("backup written to {}", dest.display()),
+            Err(e) => {
+                eprintln!("Could not back up database, error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+pub fn restore(args: &ArgMatches, mut conn: Connection, passphrase: Option<&str>) {
+    let src = path::Path::new(args.value_of("src").unwrap());
+
+    match check_backup(src, passphrase) {
+        Ok(None) => (),
+        Ok(Some(problem)) => {
+            eprintln!("{} {}. Nothing got restored!", src.display(), problem);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Could not read backup, error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    println!("This operation will replace everything in the profile with the backup");
+    if !utils::get_yn_input().unwrap() {
+        println!("Aborted");
+        process::exit(1);
+    }
+
+    // The replaced profile is kept around in case the wrong backup got picked
+    let replaced_dir = find_backup_dir(&conn).join(REPLACED_DIR);
+    fs::create_dir_all(&replaced_dir).unwrap_or_else(|err| {
+        eprintln!("Problem creating backup directory: {}", err);
+        process::exit(1);
+    });
+    let safety_copy = replaced_dir.join(format!("{}.db", timestamp()));
+    match backup_to(&conn, &safety_copy, passphrase) {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("Could not back up database before restoring, error: {}", e);
+            process::exit(1);
+        }
+    }
+
+    match restore_from(&mut conn, src, passphrase) {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!(
+                "Could not restore database, the replaced profile is at {}, error: {}",
+                safety_copy.display(),
+                e
+            );
+            process::exit(1);
+        }
+    }
+
+    // Backups made by older versions are brought up to the current schema
+    match migration::migrate(&mut conn) {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!(
+                "Could not migrate restored database, the replaced profile is at {}, error: {}",
+                safety_copy.display(),
+                e
+            );
+            process::exit(1);
+        }
+    }
+
+    match restore_files(&conn, src) {
+        Ok(missing) => {
+            println!(
+                "profile restored from {}, the replaced profile was saved to {}",
+                src.display(),
+                safety_copy.display()
+            );
+            if missing > 0 {
+                eprintln!(
+                    "{} attachments have no file in the backup, they can't be exported",
+                    missing
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!(
+                "Could not restore the attached files, the replaced profile is at {}, error: {}",
+                safety_copy.display(),
+                e
+            );
+            process::exit(1);
+        }
+    }
+}
+
+// Down to the microsecond, so a backup right before a restore doesn't get the same name
+fn timestamp() -> String {
+    let now = OffsetDateTime::now_utc();
+    format!("{}.{:06}Z", now.format("%Y%m%dT%H%M%S"), now.microsecond())
+}
+
+fn profile_name(db_path: &path::Path) -> String {
+    db_path.file_stem().map_or_else(
+        || "rtend".to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    )
+}
+
+// Rotating backups live under the data dir, in a directory named after the profile. Database
+// files elsewhere get a hash of where they are added so that two `notes.db` in different places
+// don't prune each other's backups
+pub fn backup_dir(db_path: &path::Path) -> Result<path::PathBuf, &'static str> {
+    let data_dir = utils::find_data_dir()?;
+    let name = match db_path.canonicalize() {
+        Ok(canonical)
+            if canonical.parent() != data_dir.canonicalize().ok().as_deref()
+                || canonical.extension() != Some(OsStr::new("db")) =>
+        {
+            format!(
+                "{}-{}",
+                profile_name(db_path),
+                &format!(
+                    "{:x}",
+                    Sha256::digest(canonical.to_string_lossy().as_bytes())
+                )[..16]
+            )
+        }
+        _ => profile_name(db_path),
+    };
+
+    Ok(data_dir.join("backups").join(name))
+}
+
+fn find_backup_dir(conn: &Connection) -> path::PathBuf {
+    let db_path = utils::get_db_path(conn).unwrap_or_else(|err| {
+        eprintln!("Could not find the database file, error: {}", err);
+        process::exit(1);
+    });
+    let backup_dir = backup_dir(&db_path).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    fs::create_dir_all(&backup_dir).unwrap_or_else(|err| {
+        eprintln!("Problem creating backup directory: {}", err);
+        process::exit(1);
+    });
+
+    backup_dir
+}
+
+// Attached files are copied along into the store of the backup. An existing `dest` is never
+// overwritten
+fn backup_to(
+    conn: &Connection,
+    dest: &path::Path,
+    passphrase: Option<&str>,
+) -> Result<(), StoreError> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)?;
+    let mut dest_conn = Connection::open(dest)?;
+
+    // Encrypted profiles stay encrypted with the same passphrase
+    if let Some(passphrase) = passphrase {
+        encryption::apply_key(&dest_conn, passphrase)?;
+    }
+
+    let backup = Backup::new(conn, &mut dest_conn)?;
+    backup.run_to_completion(100, Duration::from_millis(10), None)?;
+    attachment::copy_store(&utils::get_db_path(conn)?, dest)?;
+
+    Ok(())
+}
+
+// The files of the backup join the store, the ones only the replaced profile referred to leave
+// it. Returns how many attachments of the backup have no file, older backups came without them
+fn restore_files(conn: &Connection, src: &path::Path) -> Result<usize, StoreError> {
+    attachment::copy_store(src, &utils::get_db_path(conn)?)?;
+    attachment::remove_unreferenced_files(conn)?;
+
+    Ok(attachment::count_missing_files(conn)?)
+}
+
+fn restore_from(
+    conn: &mut Connection,
+    src: &path::Path,
+    passphrase: Option<&str>,
+) -> rusqlite::Result<()> {
+    let src_conn = open_backup(src, passphrase)?;
+    let backup = Backup::new(&src_conn, conn)?;
+    backup.run_to_completion(100, Duration::from_millis(10), None)
+}
+
+fn open_backup(src: &path::Path, passphrase: Option<&str>) -> rusqlite::Result<Connection> {
+    let src_conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    if let Some(passphrase) = passphrase {
+        encryption::apply_key(&src_conn, passphrase)?;
+    }
+
+    Ok(src_conn)
+}
+
+// Returns what is wrong with the backup, if anything
+fn check_backup(src: &path::Path, passphrase: Option<&str>) -> rusqlite::Result<Option<String>> {
+    if !src.is_file() {
+        return Ok(Some("does not exist".to_string()));
+    }
+
+    let src_conn = open_backup(src, passphrase)?;
+
+    let integrity: String =
+        src_conn.query_row_and_then("PRAGMA integrity_check", NO_PARAMS, |row| row.get(0))?;
+    if integrity != "ok" {
+        return Ok(Some(format!("is corrupted: {}", integrity)));
+    }
+
+    let version = migration::get_user_version(&src_conn)?;
+    if version > migration::latest_version() {
+        return Ok(Some(format!(
+            "has schema version {}, which is newer than the latest supported version {}",
+            version,
+            migration::latest_version()
+        )));
+    }
+
+    for table in REQUIRED_TABLES {
+        let count: u32 = src_conn.query_row_and_then(
+            "SELECT count(*) from sqlite_master where type = 'table' and name = (?)",
+            &[table],
+            |row| row.get(0),
+        )?;
+        if count == 0 {
+            return Ok(Some(format!(
+                "is not an rtend database, it has no `{}` table",
+                table
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+// Keeps the newest `keep` backups, returns how many were removed
+fn prune_backups(backup_dir: &path::Path, keep: usize) -> io::Result<usize> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backup_dir)? {
+        let entry_path = entry?.path();
+        if entry_path.extension() == Some(OsStr::new("db")) {
+            backups.push(entry_path);
+        }
+    }
+
+    // Timestamped names sort from oldest to newest
+    backups.sort();
+    let removed = backups.len().saturating_sub(keep);
+    for old_backup in &backups[..removed] {
+        fs::remove_file(old_backup)?;
+        if attachment::store_dir(old_backup).is_dir() {
+            fs::remove_dir_all(attachment::store_dir(old_backup))?;
+        }
+    }
+
+    Ok(removed)
+}
@@ -0,0 +1,301 @@
+use clap::ArgMatches;
+use comfy_table::presets;
+use directories::ProjectDirs;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path, process, str::FromStr};
+use time::{Format, OffsetDateTime};
+
+use crate::output::{OutputFormat, OUTPUT_FORMATS};
+use crate::utils;
+
+const CONFIG_FILE: &str = "config.toml";
+
+pub const DEFAULT_TABLE_PRESET: &str = "utf8_full";
+pub const DEFAULT_TIME_FORMAT: &str = "rfc3339";
+pub const DEFAULT_PREVIEW_COMMAND: &str = "list --entity {2} -vv";
+pub const DEFAULT_PREVIEW_WINDOW: &str = "down:50%";
+
+// Names accepted by `table_preset` and --table-preset
+pub const TABLE_PRESETS: &[(&str, &str)] = &[
+    ("utf8_full", presets::UTF8_FULL),
+    ("utf8_no_borders", presets::UTF8_NO_BORDERS),
+    ("utf8_borders_only", presets::UTF8_BORDERS_ONLY),
+    ("ascii_full", presets::ASCII_FULL),
+    ("ascii_no_borders", presets::ASCII_NO_BORDERS),
+    ("ascii_borders_only", presets::ASCII_BORDERS_ONLY),
+    ("ascii_markdown", presets::ASCII_MARKDOWN),
+    ("nothing", presets::NOTHING),
+];
+
+// Set once in main, after the command line overrides got applied
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    pub table_preset: Option<String>,
+    pub time_format: Option<String>,
+    pub format: Option<String>,
+    pub preview_command: Option<String>,
+    pub preview_window: Option<String>,
+    pub editor: Option<String>,
+    pub user_name: Option<String>,
+}
+
+impl Config {
+    fn value_of(&self, key: &str) -> Option<&str> {
+        match key {
+            "default_profile" => self.default_profile.as_deref(),
+            "table_preset" => self.table_preset.as_deref(),
+            "time_format" => self.time_format.as_deref(),
+            "format" => self.format.as_deref(),
+            "preview_command" => self.preview_command.as_deref(),
+            "preview_window" => self.preview_window.as_deref(),
+            "editor" => self.editor.as_deref(),
+            "user_name" => self.user_name.as_deref(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn set_value(&mut self, key: &str, value: Option<String>) {
+        match key {
+            "default_profile" => self.default_profile = value,
+            "table_preset" => self.table_preset = value,
+            "time_format" => self.time_format = value,
+            "format" => self.format = value,
+            "preview_command" => self.preview_command = value,
+            "preview_window" => self.preview_window = value,
+            "editor" => self.editor = value,
+            "user_name" => self.user_name = value,
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    InvalidValue(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(e) => write!(f, "{}", e),
+            ConfigError::Serialize(e) => write!(f, "{}", e),
+            ConfigError::InvalidValue(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConfigError::Serialize(e)
+    }
+}
+
+pub fn config(args: &ArgMatches) {
+    match args.subcommand() {
+        ("get", Some(get_matches)) => {
+            let config = get();
+            match get_matches.value_of("key") {
+                Some(key) => match config.value_of(key) {
+                    Some(value) => println!("{}", value),
+                    None => println!("`{}` is not set", key),
+                },
+                None => match toml::to_string(config) {
+                    Ok(content) => print!("{}", content),
+                    Err(e) => {
+                        eprintln!("Could not show the config, error: {}", e);
+                        process::exit(1);
+                    }
+                },
+            }
+        }
+
+        ("set", Some(set_matches)) => {
+            let key = set_matches.value_of("key").unwrap();
+            let value = set_matches.value_of("value").unwrap();
+
+            match set(key, Some(value)) {
+                Ok(()) => println!("`{}` set to `{}`", key, value),
+                Err(e) => {
+                    eprintln!("Could not set `{}`, error: {}", key, e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        ("unset", Some(unset_matches)) => {
+            let key = unset_matches.value_of("key").unwrap();
+
+            match set(key, None) {
+                Ok(()) => println!("`{}` unset", key),
+                Err(e) => {
+                    eprintln!("Could not unset `{}`, error: {}", key, e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        ("path", Some(_path_matches)) => match config_path() {
+            Ok(path) => println!("{}", path.display()),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+
+        _ => unreachable!(),
+    }
+}
+
+// Loads the config file and applies the global flags which override it
+pub fn init(args: &ArgMatches) {
+    let mut config = load().unwrap_or_else(|err| {
+        eprintln!("Could not read the config file, error: {}", err);
+        process::exit(1);
+    });
+
+    for key in &["table_preset", "time_format", "format"] {
+        if let Some(value) = args.value_of(key) {
+            config.set_value(key, Some(value.to_string()));
+        }
+        if let Some(value) = config.value_of(key) {
+            if let Err(e) = validate(key, value) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Only fails if it was set already, which means the config is in use
+    let _ = CONFIG.set(config);
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        load().unwrap_or_else(|err| {
+            eprintln!("Could not read the config file, error: {}", err);
+            process::exit(1);
+        })
+    })
+}
+
+pub fn config_path() -> Result<path::PathBuf, &'static str> {
+    if let Some(base_dir) = ProjectDirs::from("", "", utils::PROGRAM_NAME) {
+        Ok(base_dir.config_dir().join(CONFIG_FILE))
+    } else {
+        Err("Could not retrieve home directory. You maybe are using unsupported OS.")
+    }
+}
+
+fn load() -> Result<Config, ConfigError> {
+    let path = config_path().map_err(|e| ConfigError::InvalidValue(e.to_string()))?;
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(toml::from_str(&content)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Changes a single key of the config file, leaving the others as they are
+pub fn set(key: &str, value: Option<&str>) -> Result<(), ConfigError> {
+    if let Some(value) = value {
+        validate(key, value)?;
+    }
+
+    let mut config = load()?;
+    config.set_value(key, value.map(|v| v.to_string()));
+
+    let path = config_path().map_err(|e| ConfigError::InvalidValue(e.to_string()))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, toml::to_string(&config)?)?;
+
+    Ok(())
+}
+
+fn validate(key: &str, value: &str) -> Result<(), ConfigError> {
+    match key {
+        "default_profile" if !utils::is_valid_profile_name(value) => {
+            Err(ConfigError::InvalidValue(
+                "profile name must not be empty, start with `.` or contain path separators"
+                    .to_string(),
+            ))
+        }
+        "table_preset" if find_preset(value).is_none() => Err(ConfigError::InvalidValue(format!(
+            "unknown table preset `{}`, must be one of: {}",
+            value,
+            TABLE_PRESETS
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+        "format" if OutputFormat::from_str(value).is_err() => {
+            Err(ConfigError::InvalidValue(format!(
+                "unknown format `{}`, must be one of: {}",
+                value,
+                OUTPUT_FORMATS.join(", ")
+            )))
+        }
+        "time_format" if value != DEFAULT_TIME_FORMAT => time::validate_format_string(value)
+            .map_err(|e| ConfigError::InvalidValue(format!("invalid time format: {}", e))),
+        _ => Ok(()),
+    }
+}
+
+fn find_preset(name: &str) -> Option<&'static str> {
+    TABLE_PRESETS
+        .iter()
+        .find(|(preset_name, _)| *preset_name == name)
+        .map(|(_, preset)| *preset)
+}
+
+pub fn table_preset() -> &'static str {
+    if output_format() == OutputFormat::Markdown {
+        return presets::ASCII_MARKDOWN;
+    }
+
+    find_preset(
+        get()
+            .table_preset
+            .as_deref()
+            .unwrap_or(DEFAULT_TABLE_PRESET),
+    )
+    .unwrap_or(presets::UTF8_FULL)
+}
+
+pub fn output_format() -> OutputFormat {
+    get()
+        .format
+        .as_deref()
+        .and_then(|format| OutputFormat::from_str(format).ok())
+        .unwrap_or(OutputFormat::Table)
+}
+
+pub fn format_time(time: OffsetDateTime) -> String {
+    match get().time_format.as_deref() {
+        None | Some(DEFAULT_TIME_FORMAT) => time.format(Format::Rfc3339),
+        Some(format) => time.format(format),
+    }
+}
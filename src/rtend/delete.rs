@@ -2,6 +2,8 @@ use clap::ArgMatches;
 use rusqlite::{self, params, Connection};
 use std::{process, str::FromStr, unreachable};
 
+use crate::tag::TagKind;
+use crate::trash::{self, TrashKind};
 use crate::utils;
 
 pub fn delete(args: &ArgMatches, conn: Connection) {
@@ -13,9 +15,7 @@ pub fn delete(args: &ArgMatches, conn: Connection) {
             });
 
         if args.is_present("force") {
-            println!(
-                "This operation will force delete everything related to this entity and cannot be undone"
-            );
+            println!("This operation will move everything related to this entity to the trash");
             if utils::get_yn_input().unwrap() {
                 match force_delete_entity(conn, entity_id) {
                     Ok(()) => (),
@@ -74,7 +74,7 @@ pub fn delete(args: &ArgMatches, conn: Connection) {
 
         if args.is_present("force") {
             println!(
-            "This operation will force delete every relation snippets related to this relation_id and cannot be undone"
+            "This operation will move every relation snippet related to this relation_id to the trash"
             );
             if utils::get_yn_input().unwrap() {
                 match force_delete_relation(conn, relation_id) {
@@ -111,11 +111,68 @@ pub fn delete(args: &ArgMatches, conn: Connection) {
                 process::exit(1);
             }
         }
+    } else if args.is_present("delete_attachment") {
+        let attachment_id = u32::from_str(args.value_of("delete_attachment").unwrap())
+            .unwrap_or_else(|_err| {
+                eprintln!("attachment_id must be an u32");
+                process::exit(1);
+            });
+
+        match delete_attachment(conn, attachment_id) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not delete attachment, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("delete_property") {
+        let property_id =
+            u32::from_str(args.value_of("delete_property").unwrap()).unwrap_or_else(|_err| {
+                eprintln!("property_id must be an u32");
+                process::exit(1);
+            });
+
+        match delete_property(conn, property_id) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not delete property, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("delete_tag") {
+        let tag_args: Vec<_> = args.values_of("delete_tag").unwrap().collect();
+        let kind = TagKind::from_str(tag_args[0]).unwrap_or_else(|_err| {
+            eprintln!("kind must be either `entity` or `snippet`");
+            process::exit(1);
+        });
+        let id = u32::from_str(tag_args[1]).unwrap_or_else(|_err| {
+            eprintln!("{}_id must be an u32", kind);
+            process::exit(1);
+        });
+
+        match delete_tag(conn, kind, id, tag_args[2]) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not delete tag, error: {}", e);
+                process::exit(1);
+            }
+        }
     }
 }
 
-fn delete_entity(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
-    let rows_returned = conn.execute("DELETE from entity where id = (?)", params![entity_id])?;
+fn delete_entity(mut conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
+    // Without --force only bare entities get deleted
+    let (alias_count, snippet_count, relation_count, attachment_count, property_count) =
+        count_entity_children(&conn, entity_id)?;
+    if alias_count + snippet_count + relation_count + attachment_count + property_count > 0 {
+        println!(
+            "entity id {} still has {} aliases, {} snippets, {} relations, {} attachments and {} properties. Use --force to delete them too. Nothing got deleted!",
+            entity_id, alias_count, snippet_count, relation_count, attachment_count, property_count
+        );
+        return Ok(());
+    }
+
+    let rows_returned = trash::trash_item(&mut conn, TrashKind::Entity, entity_id)?;
 
     match rows_returned {
         0 => println!(
@@ -123,7 +180,7 @@ fn delete_entity(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
             entity_id
         ),
         1 => {
-            println!("entity id `{}` deleted", entity_id);
+            println!("entity id `{}` moved to the trash", entity_id);
         }
         _ => unreachable!(),
     }
@@ -131,13 +188,13 @@ fn delete_entity(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
     Ok(())
 }
 
-fn delete_alias(conn: Connection, alias_id: u32) -> rusqlite::Result<()> {
-    let rows_returned = conn.execute("DELETE from alias where id = (?)", params![alias_id])?;
+fn delete_alias(mut conn: Connection, alias_id: u32) -> rusqlite::Result<()> {
+    let rows_returned = trash::trash_item(&mut conn, TrashKind::Alias, alias_id)?;
 
     match rows_returned {
         0 => println!("alias id {} does not exist. Nothing got deleted!", alias_id),
         1 => {
-            println!("alias id `{}` deleted", alias_id);
+            println!("alias id `{}` moved to the trash", alias_id);
         }
         _ => unreachable!(),
     }
@@ -145,8 +202,8 @@ fn delete_alias(conn: Connection, alias_id: u32) -> rusqlite::Result<()> {
     Ok(())
 }
 
-fn delete_snippet(conn: Connection, snippet_id: u32) -> rusqlite::Result<()> {
-    let rows_returned = conn.execute("DELETE from snippet where id = (?)", params![snippet_id])?;
+fn delete_snippet(mut conn: Connection, snippet_id: u32) -> rusqlite::Result<()> {
+    let rows_returned = trash::trash_item(&mut conn, TrashKind::Snippet, snippet_id)?;
 
     match rows_returned {
         0 => println!(
@@ -154,7 +211,7 @@ fn delete_snippet(conn: Connection, snippet_id: u32) -> rusqlite::Result<()> {
             snippet_id
         ),
         1 => {
-            println!("snippet id `{}` deleted", snippet_id);
+            println!("snippet id `{}` moved to the trash", snippet_id);
         }
         _ => unreachable!(),
     }
@@ -162,9 +219,21 @@ fn delete_snippet(conn: Connection, snippet_id: u32) -> rusqlite::Result<()> {
     Ok(())
 }
 
-fn delete_relation(conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
-    let rows_returned =
-        conn.execute("DELETE from relation where id = (?)", params![relation_id])?;
+fn delete_relation(mut conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
+    let relation_snippet_count: u32 = conn.query_row_and_then(
+        "SELECT count(*) from relation_snippet where relation_id = (?) and trash_id is null",
+        params![relation_id],
+        |row| row.get(0),
+    )?;
+    if relation_snippet_count > 0 {
+        println!(
+            "relation id {} still has {} relation snippets. Use --force to delete them too. Nothing got deleted!",
+            relation_id, relation_snippet_count
+        );
+        return Ok(());
+    }
+
+    let rows_returned = trash::trash_item(&mut conn, TrashKind::Relation, relation_id)?;
 
     match rows_returned {
         0 => println!(
@@ -172,7 +241,7 @@ fn delete_relation(conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
             relation_id
         ),
         1 => {
-            println!("relation id `{}` deleted", relation_id);
+            println!("relation id `{}` moved to the trash", relation_id);
         }
         _ => unreachable!(),
     }
@@ -180,11 +249,9 @@ fn delete_relation(conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
     Ok(())
 }
 
-fn delete_relation_snippet(conn: Connection, relation_snippet_id: u32) -> rusqlite::Result<()> {
-    let rows_returned = conn.execute(
-        "DELETE from relation_snippet where id = (?)",
-        params![relation_snippet_id],
-    )?;
+fn delete_relation_snippet(mut conn: Connection, relation_snippet_id: u32) -> rusqlite::Result<()> {
+    let rows_returned =
+        trash::trash_item(&mut conn, TrashKind::RelationSnippet, relation_snippet_id)?;
 
     match rows_returned {
         0 => println!(
@@ -192,7 +259,10 @@ fn delete_relation_snippet(conn: Connection, relation_snippet_id: u32) -> rusqli
             relation_snippet_id
         ),
         1 => {
-            println!("relation snippet id `{}` deleted", relation_snippet_id);
+            println!(
+                "relation snippet id `{}` moved to the trash",
+                relation_snippet_id
+            );
         }
         _ => unreachable!(),
     }
@@ -200,66 +270,202 @@ fn delete_relation_snippet(conn: Connection, relation_snippet_id: u32) -> rusqli
     Ok(())
 }
 
-fn force_delete_entity(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
-    // Relation snippets
-    let mut rows_returned = conn.execute(
-        "DELETE from relation_snippet where relation_id in (SELECT id from relation where entity_id_a = (?1) or entity_id_b = (?1))",
-        params![entity_id],
+fn delete_attachment(mut conn: Connection, attachment_id: u32) -> rusqlite::Result<()> {
+    let rows_returned = trash::trash_item(&mut conn, TrashKind::Attachment, attachment_id)?;
+
+    match rows_returned {
+        0 => println!(
+            "attachment id {} does not exist. Nothing got deleted!",
+            attachment_id
+        ),
+        1 => {
+            println!("attachment id `{}` moved to the trash", attachment_id);
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn delete_property(mut conn: Connection, property_id: u32) -> rusqlite::Result<()> {
+    let rows_returned = trash::trash_item(&mut conn, TrashKind::Property, property_id)?;
+
+    match rows_returned {
+        0 => println!(
+            "property id {} does not exist. Nothing got deleted!",
+            property_id
+        ),
+        1 => {
+            println!("property id `{}` moved to the trash", property_id);
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn delete_tag(mut conn: Connection, kind: TagKind, id: u32, name: &str) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let rows_returned = match kind {
+        TagKind::Entity => tx.execute(
+            "DELETE from entity_tag where entity_id = (?1) and tag_id = (SELECT id from tag where name = (?2))",
+            params![id, name],
+        )?,
+        TagKind::Snippet => tx.execute(
+            "DELETE from snippet_tag where snippet_id = (?1) and tag_id = (SELECT id from tag where name = (?2))",
+            params![id, name],
+        )?,
+    };
+
+    // Tags only exist as long as something is tagged with them
+    tx.execute(
+        "DELETE from tag where id not in (SELECT tag_id from entity_tag)
+        and id not in (SELECT tag_id from snippet_tag)",
+        params![],
     )?;
-    println!(
-        "{} relation snippets of entity_id {} deleted",
-        rows_returned, entity_id
-    );
-
-    // Relations
-    rows_returned = conn.execute(
-        "DELETE from relation where id in (SELECT id from relation where entity_id_a = (?1) or entity_id_b = (?1))",
+    tx.commit()?;
+
+    match rows_returned {
+        0 => println!(
+            "{} id {} is not tagged `{}`. Nothing got deleted!",
+            kind, id, name
+        ),
+        1 => {
+            println!("tag `{}` removed from {} id `{}`", name, kind, id);
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn count_entity_children(
+    conn: &Connection,
+    entity_id: u32,
+) -> rusqlite::Result<(u32, u32, u32, u32, u32)> {
+    conn.query_row_and_then(
+        "SELECT (SELECT count(*) from alias where entity_id = (?1) and trash_id is null),
+        (SELECT count(*) from snippet where entity_id = (?1) and trash_id is null),
+        (SELECT count(*) from relation where (entity_id_a = (?1) or entity_id_b = (?1))
+            and trash_id is null),
+        (SELECT count(*) from attachment where entity_id = (?1) and trash_id is null),
+        (SELECT count(*) from property where entity_id = (?1) and trash_id is null)",
         params![entity_id],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        },
+    )
+}
+
+fn force_delete_entity(mut conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let trash_id = trash::new_trash_entry(&tx, TrashKind::Entity, entity_id)?;
+
+    // Everything shares one trash entry so it gets restored together
+    let relation_snippet_count = tx.execute(
+        "UPDATE relation_snippet set trash_id = (?1) where trash_id is null and relation_id in
+        (SELECT id from relation where (entity_id_a = (?2) or entity_id_b = (?2)) and trash_id is null)",
+        params![trash_id, entity_id],
     )?;
-    println!(
-        "{} relations of entity_id {} deleted",
-        rows_returned, entity_id
-    );
-
-    // Snippets
-    rows_returned = conn.execute(
-        "DELETE from snippet where id in (SELECT id from snippet where entity_id = (?))",
-        params![entity_id],
+    let relation_count = tx.execute(
+        "UPDATE relation set trash_id = (?1) where (entity_id_a = (?2) or entity_id_b = (?2))
+        and trash_id is null",
+        params![trash_id, entity_id],
     )?;
-    println!(
-        "{} snippets of entity_id {} deleted",
-        rows_returned, entity_id
-    );
-
-    // Aliases
-    rows_returned = conn.execute(
-        "DELETE from alias where id in (SELECT id from alias where entity_id = (?))",
-        params![entity_id],
+    let snippet_count = tx.execute(
+        "UPDATE snippet set trash_id = (?1) where entity_id = (?2) and trash_id is null",
+        params![trash_id, entity_id],
+    )?;
+    let attachment_count = tx.execute(
+        "UPDATE attachment set trash_id = (?1) where entity_id = (?2) and trash_id is null",
+        params![trash_id, entity_id],
+    )?;
+    let property_count = tx.execute(
+        "UPDATE property set trash_id = (?1) where entity_id = (?2) and trash_id is null",
+        params![trash_id, entity_id],
+    )?;
+    let alias_count = tx.execute(
+        "UPDATE alias set trash_id = (?1) where entity_id = (?2) and trash_id is null",
+        params![trash_id, entity_id],
+    )?;
+    let rows_returned = tx.execute(
+        "UPDATE entity set trash_id = (?1) where id = (?2) and trash_id is null",
+        params![trash_id, entity_id],
     )?;
-    println!(
-        "{} aliases of entity_id {} deleted",
-        rows_returned, entity_id
-    );
 
-    // Finally delete the entity itself
-    delete_entity(conn, entity_id)?;
+    match rows_returned {
+        0 => println!(
+            "entity id {} does not exist. Nothing got deleted!",
+            entity_id
+        ),
+        1 => {
+            tx.commit()?;
+            println!(
+                "{} relation snippets of entity_id {} moved to the trash",
+                relation_snippet_count, entity_id
+            );
+            println!(
+                "{} relations of entity_id {} moved to the trash",
+                relation_count, entity_id
+            );
+            println!(
+                "{} snippets of entity_id {} moved to the trash",
+                snippet_count, entity_id
+            );
+            println!(
+                "{} attachments of entity_id {} moved to the trash",
+                attachment_count, entity_id
+            );
+            println!(
+                "{} properties of entity_id {} moved to the trash",
+                property_count, entity_id
+            );
+            println!(
+                "{} aliases of entity_id {} moved to the trash",
+                alias_count, entity_id
+            );
+            println!("entity id `{}` moved to the trash", entity_id);
+        }
+        _ => unreachable!(),
+    }
 
     Ok(())
 }
 
-fn force_delete_relation(conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
-    // Relation snippets
-    let rows_returned = conn.execute(
-        "DELETE from relation_snippet where relation_id = (?)",
-        params![relation_id],
+fn force_delete_relation(mut conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let trash_id = trash::new_trash_entry(&tx, TrashKind::Relation, relation_id)?;
+
+    let relation_snippet_count = tx.execute(
+        "UPDATE relation_snippet set trash_id = (?1) where relation_id = (?2) and trash_id is null",
+        params![trash_id, relation_id],
+    )?;
+    let rows_returned = tx.execute(
+        "UPDATE relation set trash_id = (?1) where id = (?2) and trash_id is null",
+        params![trash_id, relation_id],
     )?;
-    println!(
-        "{} relation snippets of relation_id {} deleted",
-        rows_returned, relation_id
-    );
 
-    // Then the relation itself
-    delete_relation(conn, relation_id)?;
+    match rows_returned {
+        0 => println!(
+            "relation id {} does not exist. Nothing got deleted!",
+            relation_id
+        ),
+        1 => {
+            tx.commit()?;
+            println!(
+                "{} relation snippets of relation_id {} moved to the trash",
+                relation_snippet_count, relation_id
+            );
+            println!("relation id `{}` moved to the trash", relation_id);
+        }
+        _ => unreachable!(),
+    }
 
     Ok(())
 }
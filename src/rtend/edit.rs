@@ -1,8 +1,29 @@
 use clap::ArgMatches;
 use rusqlite::{self, params, Connection};
-use std::{process, str::FromStr};
+use std::{env, process, str::FromStr};
+
+use crate::agenda;
+use crate::config;
+use crate::link;
+use crate::property::{self, PropertyType};
+use crate::revision::{self, SnippetKind};
 
 pub fn edit(args: &ArgMatches, conn: Connection) {
+    // scrawl opens whatever $EDITOR points to
+    if let Some(editor) = args
+        .value_of("editor")
+        .or_else(|| config::get().editor.as_deref())
+    {
+        env::set_var("EDITOR", editor);
+    }
+
+    if args.is_present("due")
+        && !(args.is_present("edit_snippet") || args.is_present("edit_relation_snippet"))
+    {
+        eprintln!("--due can only be used with --snippet or --relation-snippet");
+        process::exit(1);
+    }
+
     if args.is_present("edit_alias") {
         let id = u32::from_str(args.value_of("edit_alias").unwrap()).unwrap_or_else(|_err| {
             eprintln!("alias_id must be an u32");
@@ -20,7 +41,15 @@ pub fn edit(args: &ArgMatches, conn: Connection) {
             eprintln!("snippet_id must be an u32");
             process::exit(1);
         });
-        match update_snippet(conn, id) {
+        // With --due only the due date changes, the editor isn't opened
+        let result = match args.value_of("due") {
+            Some(due) => {
+                let due = agenda::get_due(&conn, due);
+                agenda::set_due(&conn, SnippetKind::Snippet, id, due.as_deref())
+            }
+            None => update_snippet(conn, id),
+        };
+        match result {
             Ok(()) => (),
             Err(e) => {
                 eprintln!("Could not update snippet, error: {}", e);
@@ -33,19 +62,64 @@ pub fn edit(args: &ArgMatches, conn: Connection) {
                 eprintln!("relation_snippet_id must be an u32");
                 process::exit(1);
             });
-        match update_relation_snippet(conn, id) {
+        let result = match args.value_of("due") {
+            Some(due) => {
+                let due = agenda::get_due(&conn, due);
+                agenda::set_due(&conn, SnippetKind::RelationSnippet, id, due.as_deref())
+            }
+            None => update_relation_snippet(conn, id),
+        };
+        match result {
             Ok(()) => (),
             Err(e) => {
                 eprintln!("Could not update relation snippet, error: {}", e);
                 process::exit(1);
             }
         }
+    } else if args.is_present("edit_property") {
+        let id = u32::from_str(args.value_of("edit_property").unwrap()).unwrap_or_else(|_err| {
+            eprintln!("property_id must be an u32");
+            process::exit(1);
+        });
+        let kind = args
+            .value_of("property_type")
+            .map(|kind| PropertyType::from_str(kind).unwrap());
+        match update_property(conn, id, kind) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not update property, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("edit_revert") || args.is_present("edit_relation_revert") {
+        let (kind, arg) = if args.is_present("edit_revert") {
+            (SnippetKind::Snippet, "edit_revert")
+        } else {
+            (SnippetKind::RelationSnippet, "edit_relation_revert")
+        };
+        let revert_args: Vec<_> = args.values_of(arg).unwrap().collect();
+        let id = u32::from_str(revert_args[0]).unwrap_or_else(|_err| {
+            eprintln!("snippet_id must be an u32");
+            process::exit(1);
+        });
+        let revision = u32::from_str(revert_args[1]).unwrap_or_else(|_err| {
+            eprintln!("revision must be an u32");
+            process::exit(1);
+        });
+
+        match revert_snippet(conn, kind, id, revision) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not revert snippet, error: {}", e);
+                process::exit(1);
+            }
+        }
     }
 }
 
 fn update_alias(conn: Connection, id: u32) -> rusqlite::Result<()> {
     let old_data: String = conn.query_row_and_then(
-        "SELECT name from alias where id = (?)",
+        "SELECT name from alias where id = (?) and trash_id is null",
         params![id],
         |data| data.get(0),
     )?;
@@ -73,7 +147,7 @@ fn update_alias(conn: Connection, id: u32) -> rusqlite::Result<()> {
 
 fn update_snippet(conn: Connection, id: u32) -> rusqlite::Result<()> {
     let old_data: String = conn.query_row_and_then(
-        "SELECT data from snippet where id = (?)",
+        "SELECT data from snippet where id = (?) and trash_id is null",
         params![id],
         |data| data.get(0),
     )?;
@@ -87,6 +161,7 @@ fn update_snippet(conn: Connection, id: u32) -> rusqlite::Result<()> {
         "UPDATE snippet set data = (?1), updated = datetime('now') where id = (?2)",
         params![edited_data, id],
     )?;
+    link::update_links(&conn, SnippetKind::Snippet, id, &edited_data)?;
 
     match rows_returned {
         1 => {
@@ -100,7 +175,7 @@ fn update_snippet(conn: Connection, id: u32) -> rusqlite::Result<()> {
 
 fn update_relation_snippet(conn: Connection, id: u32) -> rusqlite::Result<()> {
     let old_data: String = conn.query_row_and_then(
-        "SELECT data from relation_snippet where id = (?)",
+        "SELECT data from relation_snippet where id = (?) and trash_id is null",
         params![id],
         |data| data.get(0),
     )?;
@@ -114,6 +189,7 @@ fn update_relation_snippet(conn: Connection, id: u32) -> rusqlite::Result<()> {
         "UPDATE relation_snippet set data = (?1), updated = datetime('now') where id = (?2)",
         params![edited_data, id],
     )?;
+    link::update_links(&conn, SnippetKind::RelationSnippet, id, &edited_data)?;
 
     match rows_returned {
         1 => {
@@ -124,3 +200,98 @@ fn update_relation_snippet(conn: Connection, id: u32) -> rusqlite::Result<()> {
 
     Ok(())
 }
+
+// The value keeps its type unless another one is given
+fn update_property(conn: Connection, id: u32, kind: Option<PropertyType>) -> rusqlite::Result<()> {
+    let (old_data, old_kind): (String, String) = conn.query_row(
+        &format!(
+            "SELECT {}, type from property where id = (?) and trash_id is null",
+            property::DISPLAY_VALUE
+        ),
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let edited_data = scrawl::with(&old_data).expect("Could not open editor");
+    let edited_data = edited_data.trim();
+    if edited_data.is_empty() {
+        eprintln!("Edited data is empty. Aborted");
+        process::exit(1);
+    }
+
+    let kind = kind.or_else(|| PropertyType::from_str(&old_kind).ok());
+    let (kind, value) = property::get_value(edited_data, kind);
+    let rows_returned = conn.execute(
+        "UPDATE property set type = (?1), value = (?2), updated = datetime('now') where id = (?3)",
+        params![kind.to_string(), value, id],
+    )?;
+
+    match rows_returned {
+        1 => {
+            println!("property id `{}` updated", id);
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+// Reverting is just another edit, so the text being replaced is kept as a new revision
+fn revert_snippet(
+    conn: Connection,
+    kind: SnippetKind,
+    id: u32,
+    revision: u32,
+) -> rusqlite::Result<()> {
+    let table = kind.table();
+    // Snippets in the trash have no revisions to go back to
+    let revisions = revision::get_revisions(&conn, kind, id)?;
+    if revisions.is_empty() {
+        println!(
+            "{} id {} does not exist. Nothing got reverted!",
+            table.replace('_', " "),
+            id
+        );
+        return Ok(());
+    }
+
+    let old_data = match revisions.into_iter().find(|rev| rev.revision == revision) {
+        Some(rev) => rev.data,
+        None => {
+            println!(
+                "revision {} of {} id {} does not exist. Nothing got reverted!",
+                revision,
+                table.replace('_', " "),
+                id
+            );
+            return Ok(());
+        }
+    };
+
+    let rows_returned = conn.execute(
+        &format!(
+            "UPDATE {} set data = (?1), updated = datetime('now') where id = (?2) and trash_id is null",
+            table
+        ),
+        params![old_data, id],
+    )?;
+
+    match rows_returned {
+        0 => println!(
+            "{} id {} does not exist. Nothing got reverted!",
+            table.replace('_', " "),
+            id
+        ),
+        1 => {
+            link::update_links(&conn, kind, id, &old_data)?;
+            println!(
+                "{} id `{}` reverted to revision {}",
+                table.replace('_', " "),
+                id,
+                revision
+            );
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
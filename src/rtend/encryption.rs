@@ -0,0 +1,156 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, Connection, NO_PARAMS};
+use std::{
+    env, fs,
+    io::{self, Read},
+    path, process,
+};
+
+use crate::migration;
+use crate::utils;
+
+// Passphrase sources, checked after the --keyfile flag and before prompting
+pub const KEYFILE_ENV: &str = "RTEND_KEYFILE";
+pub const PASSPHRASE_ENV: &str = "RTEND_PASSPHRASE";
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+pub fn rekey(args: &ArgMatches, conn: Connection) {
+    check_support();
+
+    let passphrase = get_new_passphrase(args.value_of("new_keyfile")).unwrap_or_else(|err| {
+        eprintln!("Could not read the new passphrase, error: {}", err);
+        process::exit(1);
+    });
+    let db_path = utils::get_db_path(&conn).unwrap_or_else(|err| {
+        eprintln!("Could not find the database file, error: {}", err);
+        process::exit(1);
+    });
+
+    if is_encrypted(&db_path) {
+        match conn.pragma_update(None, "rekey", &passphrase) {
+            Ok(()) => println!("passphrase of {} changed", db_path.display()),
+            Err(e) => {
+                eprintln!("Could not change the passphrase, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        // Plaintext databases cannot be rekeyed in place, an encrypted copy replaces them instead
+        let mut tmp_path = db_path.clone().into_os_string();
+        tmp_path.push(".encrypted");
+        let tmp_path = path::PathBuf::from(tmp_path);
+
+        match export_encrypted(conn, &passphrase, &tmp_path) {
+            Ok(()) => (),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                eprintln!("Could not encrypt the database, error: {}", e);
+                process::exit(1);
+            }
+        }
+        match fs::rename(&tmp_path, &db_path) {
+            Ok(()) => println!("{} is now encrypted", db_path.display()),
+            Err(e) => {
+                eprintln!(
+                    "Could not replace the database with its encrypted copy at {}, error: {}",
+                    tmp_path.display(),
+                    e
+                );
+                process::exit(1);
+            }
+        }
+    }
+}
+
+// Exits when encryption is asked for but rtend was built against plain SQLite
+pub fn check_support() {
+    if !cfg!(feature = "sqlcipher") {
+        eprintln!(
+            "rtend was built without SQLCipher support, rebuild it with `--no-default-features --features sqlcipher`"
+        );
+        process::exit(1);
+    }
+}
+
+// Encrypted databases do not start with the plain SQLite header
+pub fn is_encrypted(db_path: &path::Path) -> bool {
+    let mut header = [0; 16];
+    match fs::File::open(db_path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+pub fn get_passphrase(keyfile: Option<&str>) -> io::Result<String> {
+    if let Some(keyfile) = keyfile {
+        read_keyfile(keyfile)
+    } else if let Ok(keyfile) = env::var(KEYFILE_ENV) {
+        read_keyfile(&keyfile)
+    } else if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        Ok(passphrase)
+    } else {
+        rpassword::read_password_from_tty(Some("Passphrase: "))
+    }
+}
+
+// Prompted passphrases are asked twice so a typo does not lock the profile
+pub fn get_new_passphrase(keyfile: Option<&str>) -> io::Result<String> {
+    let passphrase = match keyfile {
+        Some(keyfile) => read_keyfile(keyfile)?,
+        None => {
+            let passphrase = rpassword::read_password_from_tty(Some("New passphrase: "))?;
+            let confirmation = rpassword::read_password_from_tty(Some("Repeat passphrase: "))?;
+            if passphrase != confirmation {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "passphrases do not match",
+                ));
+            }
+            passphrase
+        }
+    };
+
+    if passphrase.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "passphrase is empty",
+        ));
+    }
+
+    Ok(passphrase)
+}
+
+fn read_keyfile(keyfile: &str) -> io::Result<String> {
+    let mut passphrase = fs::read_to_string(keyfile)?;
+    Ok(utils::trim_trailing_newline(&mut passphrase))
+}
+
+// Has to be the first statement on the connection
+pub fn apply_key(conn: &Connection, passphrase: &str) -> rusqlite::Result<()> {
+    conn.pragma_update(None, "key", &passphrase)?;
+
+    // A wrong passphrase only shows once something is read
+    conn.query_row_and_then("SELECT count(*) from sqlite_master", NO_PARAMS, |row| {
+        row.get::<_, i64>(0)
+    })?;
+
+    Ok(())
+}
+
+fn export_encrypted(conn: Connection, passphrase: &str, dest: &path::Path) -> rusqlite::Result<()> {
+    conn.execute(
+        "ATTACH DATABASE (?1) AS encrypted KEY (?2)",
+        params![dest.to_string_lossy(), passphrase],
+    )?;
+    conn.query_row_and_then("SELECT sqlcipher_export('encrypted')", NO_PARAMS, |row| {
+        row.get::<_, Option<String>>(0)
+    })?;
+    conn.execute_batch(&format!(
+        "PRAGMA encrypted.user_version = {}",
+        migration::get_user_version(&conn)?
+    ))?;
+    conn.execute_batch("DETACH DATABASE encrypted")?;
+
+    Ok(())
+}
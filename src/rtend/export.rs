@@ -0,0 +1,834 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, Connection, OptionalExtension, Transaction, NO_PARAMS};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, fs, io, path, process, str::FromStr};
+use time::{Format, OffsetDateTime, UtcOffset};
+
+use crate::add;
+use crate::link;
+use crate::markdown;
+use crate::property::{self, PropertyType};
+use crate::revision::SnippetKind;
+
+// Bumped whenever a document changes in a way older versions can't import
+pub const DOCUMENT_VERSION: u32 = 1;
+
+// How timestamps are stored, `current_timestamp` writes UTC without an offset
+const STORED_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug)]
+pub enum DocumentError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Sqlite(rusqlite::Error),
+    // The document can't be imported the way it is
+    Invalid(String),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DocumentError::Io(e) => write!(f, "{}", e),
+            DocumentError::Json(e) => write!(f, "{}", e),
+            DocumentError::Sqlite(e) => write!(f, "{}", e),
+            DocumentError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for DocumentError {
+    fn from(e: io::Error) -> Self {
+        DocumentError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(e: serde_json::Error) -> Self {
+        DocumentError::Json(e)
+    }
+}
+
+impl From<rusqlite::Error> for DocumentError {
+    fn from(e: rusqlite::Error) -> Self {
+        DocumentError::Sqlite(e)
+    }
+}
+
+// Everything live in a profile, ids are the ones of the profile it was exported from and
+// only tie the document together
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub exported: String,
+    pub entities: Vec<EntityDocument>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EntityDocument {
+    pub id: u32,
+    pub created: String,
+    pub aliases: Vec<AliasDocument>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub properties: Vec<PropertyDocument>,
+    #[serde(default)]
+    pub snippets: Vec<SnippetDocument>,
+    // Relations are listed under their first entity
+    #[serde(default)]
+    pub relations: Vec<RelationDocument>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AliasDocument {
+    pub name: String,
+    pub created: String,
+    pub updated: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PropertyDocument {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub value: String,
+    pub created: String,
+    pub updated: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SnippetDocument {
+    pub data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub created: String,
+    pub updated: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RelationDocument {
+    pub entity_id: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inverse_label: Option<String>,
+    pub created: String,
+    pub updated: String,
+    #[serde(default)]
+    pub snippets: Vec<SnippetDocument>,
+}
+
+// The global --format picks the kind of document, only json and markdown are documents
+fn document_format<'a>(args: &'a ArgMatches) -> &'a str {
+    match args.value_of("format") {
+        None => "json",
+        Some(format @ "json") | Some(format @ "markdown") => format,
+        Some(format) => {
+            eprintln!(
+                "`{}` is not a document format, use json or markdown",
+                format
+            );
+            process::exit(1);
+        }
+    }
+}
+
+pub fn export(args: &ArgMatches, conn: Connection) {
+    if document_format(args) == "markdown" {
+        let dir = match args.value_of("dest") {
+            Some(dir) => path::Path::new(dir),
+            None => {
+                eprintln!("a directory to export to is needed with --format markdown");
+                process::exit(1);
+            }
+        };
+        match markdown::export_vault(&conn, dir) {
+            Ok((written, 0)) => println!("{} entities exported to {}", written, dir.display()),
+            Ok((written, removed)) => println!(
+                "{} entities exported to {}, {} files of entities no longer there removed",
+                written,
+                dir.display(),
+                removed
+            ),
+            Err(e) => {
+                eprintln!("Could not export the profile, error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let document = read_document(&conn).unwrap_or_else(|err| {
+        eprintln!("Could not read the profile, error: {}", err);
+        process::exit(1);
+    });
+
+    match args.value_of("dest") {
+        Some(dest) => {
+            let dest = path::Path::new(dest);
+            if dest.exists() {
+                println!("{} already exists. Nothing got exported!", dest.display());
+                return;
+            }
+            match write_document(&document, dest) {
+                Ok(()) => println!(
+                    "{} entities exported to {}",
+                    document.entities.len(),
+                    dest.display()
+                ),
+                Err(e) => {
+                    eprintln!("Could not export the profile, error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => match serde_json::to_string_pretty(&document) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Could not export the profile, error: {}", e);
+                process::exit(1);
+            }
+        },
+    }
+}
+
+pub fn import(args: &ArgMatches, mut conn: Connection) {
+    let src = path::Path::new(args.value_of("src").unwrap());
+    let read = match document_format(args) {
+        "markdown" => markdown::read_vault(src),
+        _ => read_file(src).map(|document| (document, Vec::new())),
+    };
+    let (document, problems) = read.unwrap_or_else(|err| {
+        eprintln!("Could not read {}, error: {}", src.display(), err);
+        process::exit(1);
+    });
+
+    match import_document(&mut conn, &document, args.is_present("dry_run")) {
+        Ok(mut report) => {
+            report.conflicts.splice(0..0, problems);
+            report.print();
+        }
+        Err(e) => {
+            eprintln!(
+                "Could not import {}, error: {}. Nothing got imported!",
+                src.display(),
+                e
+            );
+            process::exit(1);
+        }
+    }
+}
+
+fn write_document(document: &Document, dest: &path::Path) -> Result<(), DocumentError> {
+    let mut json = serde_json::to_string_pretty(document)?;
+    json.push('\n');
+    fs::write(dest, json)?;
+
+    Ok(())
+}
+
+fn read_file(src: &path::Path) -> Result<Document, DocumentError> {
+    let document: Document = serde_json::from_str(&fs::read_to_string(src)?)?;
+    if document.version > DOCUMENT_VERSION {
+        return Err(DocumentError::Invalid(format!(
+            "document version {} is newer than this version of rtend can import",
+            document.version
+        )));
+    }
+
+    Ok(document)
+}
+
+pub fn to_rfc3339(time: OffsetDateTime) -> String {
+    time.format(Format::Rfc3339)
+}
+
+// Timestamps of a document go back into the profile the way SQLite writes them
+fn to_stored_time(time: &str) -> Result<String, DocumentError> {
+    OffsetDateTime::parse(time, Format::Rfc3339)
+        .map(|time| time.to_offset(UtcOffset::UTC).format(STORED_TIME_FORMAT))
+        .map_err(|_err| DocumentError::Invalid(format!("`{}` is not an RFC 3339 timestamp", time)))
+}
+
+fn read_tags(conn: &Connection, sql: &str, id: u32) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare(sql)?;
+    let tags = stmt
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    Ok(tags)
+}
+
+fn read_snippets(
+    conn: &Connection,
+    kind: SnippetKind,
+    owner_id: u32,
+) -> rusqlite::Result<Vec<SnippetDocument>> {
+    let owner_column = match kind {
+        SnippetKind::Snippet => "entity_id",
+        SnippetKind::RelationSnippet => "relation_id",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, data, due, created, updated from {} where {} = (?) and trash_id is null
+        order by id",
+        kind.table(),
+        owner_column
+    ))?;
+    let snippets = stmt
+        .query_map(params![owner_id], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                SnippetDocument {
+                    data: row.get(1)?,
+                    due: row.get(2)?,
+                    tags: Vec::new(),
+                    created: to_rfc3339(row.get(3)?),
+                    updated: to_rfc3339(row.get(4)?),
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut documents = Vec::new();
+    for (id, mut snippet) in snippets {
+        if let SnippetKind::Snippet = kind {
+            snippet.tags = read_tags(
+                conn,
+                "SELECT tag.name from snippet_tag join tag on tag.id = snippet_tag.tag_id
+                where snippet_tag.snippet_id = (?) order by tag.name",
+                id,
+            )?;
+        }
+        documents.push(snippet);
+    }
+
+    Ok(documents)
+}
+
+pub fn read_document(conn: &Connection) -> rusqlite::Result<Document> {
+    let mut stmt =
+        conn.prepare("SELECT id, created from entity where trash_id is null order by id")?;
+    let entities = stmt
+        .query_map(NO_PARAMS, |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, OffsetDateTime>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut documents = Vec::new();
+    for (id, created) in entities {
+        let mut stmt = conn.prepare(
+            "SELECT name, created, updated from alias where entity_id = (?)
+            and trash_id is null order by id",
+        )?;
+        let aliases = stmt
+            .query_map(params![id], |row| {
+                Ok(AliasDocument {
+                    name: row.get(0)?,
+                    created: to_rfc3339(row.get(1)?),
+                    updated: to_rfc3339(row.get(2)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT key, type, {}, created, updated from property where entity_id = (?)
+            and trash_id is null order by key",
+            property::DISPLAY_VALUE
+        ))?;
+        let properties = stmt
+            .query_map(params![id], |row| {
+                Ok(PropertyDocument {
+                    key: row.get(0)?,
+                    kind: row.get(1)?,
+                    value: row.get(2)?,
+                    created: to_rfc3339(row.get(3)?),
+                    updated: to_rfc3339(row.get(4)?),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        // Relations to an entity in the trash are left out along with it
+        let mut stmt = conn.prepare(
+            "SELECT relation.id, entity_id_b, label, inverse_label, relation.created,
+            relation.updated from relation
+            left join relation_type on relation_type.id = relation.relation_type_id
+            where entity_id_a = (?) and relation.trash_id is null
+            and (SELECT trash_id from entity where id = entity_id_b) is null
+            order by relation.id",
+        )?;
+        let relations = stmt
+            .query_map(params![id], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    RelationDocument {
+                        entity_id: row.get(1)?,
+                        label: row.get(2)?,
+                        inverse_label: row.get(3)?,
+                        created: to_rfc3339(row.get(4)?),
+                        updated: to_rfc3339(row.get(5)?),
+                        snippets: Vec::new(),
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut relation_documents = Vec::new();
+        for (relation_id, mut relation) in relations {
+            relation.snippets = read_snippets(conn, SnippetKind::RelationSnippet, relation_id)?;
+            relation_documents.push(relation);
+        }
+
+        documents.push(EntityDocument {
+            id,
+            created: to_rfc3339(created),
+            aliases,
+            tags: read_tags(
+                conn,
+                "SELECT tag.name from entity_tag join tag on tag.id = entity_tag.tag_id
+                where entity_tag.entity_id = (?) order by tag.name",
+                id,
+            )?,
+            properties,
+            snippets: read_snippets(conn, SnippetKind::Snippet, id)?,
+            relations: relation_documents,
+        });
+    }
+
+    Ok(Document {
+        version: DOCUMENT_VERSION,
+        exported: to_rfc3339(OffsetDateTime::now_utc()),
+        entities: documents,
+    })
+}
+
+// What an import did, and what it left alone because the profile already had it otherwise
+#[derive(Default)]
+pub struct ImportReport {
+    pub entities: usize,
+    pub matched_entities: usize,
+    pub aliases: usize,
+    pub properties: usize,
+    pub snippets: usize,
+    pub relations: usize,
+    pub relation_snippets: usize,
+    pub skipped: usize,
+    pub conflicts: Vec<String>,
+    // What happens to every entity and relation, only shown by a dry run
+    pub planned: Vec<String>,
+    pub dry_run: bool,
+}
+
+impl ImportReport {
+    pub fn print(&self) {
+        if self.dry_run {
+            for line in &self.planned {
+                println!("{}", line);
+            }
+        }
+        println!(
+            "{} entities imported, {} of them into existing entities",
+            self.entities, self.matched_entities
+        );
+        println!(
+            "{} aliases, {} properties, {} snippets, {} relations and {} relation snippets added",
+            self.aliases, self.properties, self.snippets, self.relations, self.relation_snippets
+        );
+        if self.skipped > 0 {
+            println!(
+                "{} things the profile already had were skipped",
+                self.skipped
+            );
+        }
+        if !self.conflicts.is_empty() {
+            println!("{} conflicts", self.conflicts.len());
+            for conflict in &self.conflicts {
+                println!("  {}", conflict);
+            }
+        }
+        if self.dry_run {
+            println!("This was a dry run, nothing got imported.");
+        }
+    }
+}
+
+// How entities of the document are called in a report
+fn entity_name(entity: &EntityDocument) -> String {
+    match entity.aliases.first() {
+        Some(alias) => format!("`{}`", alias.name),
+        None => format!("entity {} of the document", entity.id),
+    }
+}
+
+// The live entity sharing an alias with the imported one, if there is exactly one
+fn match_entity(
+    tx: &Transaction,
+    entity: &EntityDocument,
+    report: &mut ImportReport,
+) -> rusqlite::Result<Option<u32>> {
+    let mut stmt = tx.prepare(
+        "SELECT distinct entity_id from alias where name = (?) collate nocase
+        and trash_id is null",
+    )?;
+    let mut matches: Vec<u32> = Vec::new();
+    for alias in &entity.aliases {
+        for entity_id in stmt.query_map(params![alias.name], |row| row.get(0))? {
+            let entity_id = entity_id?;
+            if !matches.contains(&entity_id) {
+                matches.push(entity_id);
+            }
+        }
+    }
+
+    match matches.len() {
+        0 => Ok(None),
+        1 => Ok(Some(matches[0])),
+        _ => {
+            matches.sort_unstable();
+            report.conflicts.push(format!(
+                "aliases of entity {} belong to the entities {}, it got imported as a new entity",
+                entity.id,
+                matches
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+            Ok(None)
+        }
+    }
+}
+
+fn insert_snippet(
+    tx: &Transaction,
+    kind: SnippetKind,
+    owner_id: u32,
+    snippet: &SnippetDocument,
+    ids: &HashMap<u32, u32>,
+) -> Result<u32, DocumentError> {
+    let owner_column = match kind {
+        SnippetKind::Snippet => "entity_id",
+        SnippetKind::RelationSnippet => "relation_id",
+    };
+    let data = link::remap_ids(&snippet.data, ids);
+    tx.execute(
+        &format!(
+            "INSERT INTO {} ({}, data, due, created, updated) VALUES (?1, ?2, ?3, ?4, ?5)",
+            kind.table(),
+            owner_column
+        ),
+        params![
+            owner_id,
+            data,
+            snippet.due,
+            to_stored_time(&snippet.created)?,
+            to_stored_time(&snippet.updated)?
+        ],
+    )?;
+    let id = tx.last_insert_rowid() as u32;
+    link::update_links(tx, kind, id, &data)?;
+
+    Ok(id)
+}
+
+fn add_tags(tx: &Transaction, sql: &str, id: u32, tags: &[String]) -> rusqlite::Result<()> {
+    for tag in tags {
+        tx.execute("INSERT OR IGNORE INTO tag (name) VALUES (?)", params![tag])?;
+        tx.execute(sql, params![id, tag])?;
+    }
+
+    Ok(())
+}
+
+fn import_entity(
+    tx: &Transaction,
+    entity: &EntityDocument,
+    existing_id: Option<u32>,
+    ids: &HashMap<u32, u32>,
+    report: &mut ImportReport,
+) -> Result<(), DocumentError> {
+    let entity_id = ids[&entity.id];
+
+    for alias in &entity.aliases {
+        let known: bool = tx.query_row(
+            "SELECT exists (SELECT 1 from alias where entity_id = (?1) and name = (?2) collate nocase
+            and trash_id is null)",
+            params![entity_id, alias.name],
+            |row| row.get(0),
+        )?;
+        if known {
+            report.skipped += 1;
+            continue;
+        }
+        tx.execute(
+            "INSERT INTO alias (entity_id, name, created, updated) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                entity_id,
+                alias.name,
+                to_stored_time(&alias.created)?,
+                to_stored_time(&alias.updated)?
+            ],
+        )?;
+        report.aliases += 1;
+    }
+
+    add_tags(
+        tx,
+        "INSERT OR IGNORE INTO entity_tag (entity_id, tag_id) VALUES
+            (?1, (SELECT id from tag where name = ?2))",
+        entity_id,
+        &entity.tags,
+    )?;
+
+    for prop in &entity.properties {
+        let kind = PropertyType::from_str(&prop.kind).map_err(|_err| {
+            DocumentError::Invalid(format!("`{}` is not a property type", prop.kind))
+        })?;
+        let value = kind.parse_value(&prop.value).ok_or_else(|| {
+            DocumentError::Invalid(format!("`{}` is not a valid {}", prop.value, kind))
+        })?;
+        let existing: Option<String> = tx
+            .query_row(
+                &format!(
+                    "SELECT {} from property where entity_id = (?1) and key = (?2)
+                    and trash_id is null",
+                    property::DISPLAY_VALUE
+                ),
+                params![entity_id, prop.key],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match existing {
+            Some(existing) if existing == prop.value => report.skipped += 1,
+            Some(existing) => report.conflicts.push(format!(
+                "entity {} already has the property `{}` as `{}`, `{}` was not imported",
+                entity_id, prop.key, existing, prop.value
+            )),
+            None => {
+                tx.execute(
+                    "INSERT INTO property (entity_id, key, type, value, created, updated)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        entity_id,
+                        prop.key,
+                        kind.to_string(),
+                        value,
+                        to_stored_time(&prop.created)?,
+                        to_stored_time(&prop.updated)?
+                    ],
+                )?;
+                report.properties += 1;
+            }
+        }
+    }
+
+    for snippet in &entity.snippets {
+        // Importing the same document twice doesn't double up what is already there
+        if existing_id.is_some() {
+            let known: bool = tx.query_row(
+                "SELECT exists (SELECT 1 from snippet where entity_id = (?1) and data = (?2)
+                and trash_id is null)",
+                params![entity_id, link::remap_ids(&snippet.data, ids)],
+                |row| row.get(0),
+            )?;
+            if known {
+                report.skipped += 1;
+                continue;
+            }
+        }
+        let snippet_id = insert_snippet(tx, SnippetKind::Snippet, entity_id, snippet, ids)?;
+        add_tags(
+            tx,
+            "INSERT OR IGNORE INTO snippet_tag (snippet_id, tag_id) VALUES
+                (?1, (SELECT id from tag where name = ?2))",
+            snippet_id,
+            &snippet.tags,
+        )?;
+        report.snippets += 1;
+    }
+
+    Ok(())
+}
+
+fn import_relation(
+    tx: &Transaction,
+    entity: &EntityDocument,
+    relation: &RelationDocument,
+    ids: &HashMap<u32, u32>,
+    names: &HashMap<u32, String>,
+    report: &mut ImportReport,
+) -> Result<(), DocumentError> {
+    let (id_a, id_b) = match ids.get(&relation.entity_id) {
+        Some(id_b) => (ids[&entity.id], *id_b),
+        None => {
+            report.conflicts.push(format!(
+                "a relation of entity {} points to entity {} which is not in the document, it was not imported",
+                entity.id, relation.entity_id
+            ));
+            return Ok(());
+        }
+    };
+
+    // The profile keeps the inverse label it has, every relation of the type would read
+    // differently otherwise
+    let relation_type_id = match &relation.label {
+        Some(label) => {
+            let (relation_type_id, stored) =
+                add::find_or_add_relation_type(tx, label, relation.inverse_label.as_deref())?;
+            if let Some(stored) = stored {
+                let conflict = format!(
+                    "relation label `{}` has the inverse label `{}` in the profile, `{}` was not imported",
+                    label,
+                    stored,
+                    relation.inverse_label.as_deref().unwrap_or_default()
+                );
+                if !report.conflicts.contains(&conflict) {
+                    report.conflicts.push(conflict);
+                }
+            }
+            Some(relation_type_id)
+        }
+        None => None,
+    };
+    let existing_id: Option<u32> = tx
+        .query_row(
+            "SELECT id from relation where entity_id_a = (?1) and entity_id_b = (?2)
+            and relation_type_id is (?3) and trash_id is null order by id limit 1",
+            params![id_a, id_b, relation_type_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    let relation_id = match existing_id {
+        Some(relation_id) => {
+            report.skipped += 1;
+            relation_id
+        }
+        None => {
+            tx.execute(
+                "INSERT INTO relation (entity_id_a, entity_id_b, relation_type_id, created, updated)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    id_a,
+                    id_b,
+                    relation_type_id,
+                    to_stored_time(&relation.created)?,
+                    to_stored_time(&relation.updated)?
+                ],
+            )?;
+            report.relations += 1;
+            report.planned.push(match &relation.label {
+                Some(label) => format!(
+                    "{} {} {} becomes a new relation",
+                    names[&entity.id], label, names[&relation.entity_id]
+                ),
+                None => format!(
+                    "{} and {} get a new relation",
+                    names[&entity.id], names[&relation.entity_id]
+                ),
+            });
+            tx.last_insert_rowid() as u32
+        }
+    };
+
+    for snippet in &relation.snippets {
+        if existing_id.is_some() {
+            let known: bool = tx.query_row(
+                "SELECT exists (SELECT 1 from relation_snippet where relation_id = (?1)
+                and data = (?2) and trash_id is null)",
+                params![relation_id, link::remap_ids(&snippet.data, ids)],
+                |row| row.get(0),
+            )?;
+            if known {
+                report.skipped += 1;
+                continue;
+            }
+        }
+        insert_snippet(tx, SnippetKind::RelationSnippet, relation_id, snippet, ids)?;
+        report.relation_snippets += 1;
+    }
+
+    Ok(())
+}
+
+// Entities sharing an alias with exactly one live entity are imported into that one, every
+// other entity gets a new id. Nothing is imported if anything in the document is invalid
+pub fn import_document(
+    conn: &mut Connection,
+    document: &Document,
+    dry_run: bool,
+) -> Result<ImportReport, DocumentError> {
+    let tx = conn.transaction()?;
+    let mut report = ImportReport {
+        dry_run,
+        ..Default::default()
+    };
+    let mut ids: HashMap<u32, u32> = HashMap::new();
+    let mut existing_ids: HashMap<u32, Option<u32>> = HashMap::new();
+
+    for entity in &document.entities {
+        if ids.contains_key(&entity.id) {
+            return Err(DocumentError::Invalid(format!(
+                "entity {} is in the document more than once",
+                entity.id
+            )));
+        }
+        let existing_id = match_entity(&tx, entity, &mut report)?;
+        let entity_id = match existing_id {
+            Some(entity_id) => {
+                report.matched_entities += 1;
+                entity_id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO entity (created) VALUES (?)",
+                    params![to_stored_time(&entity.created)?],
+                )?;
+                tx.last_insert_rowid() as u32
+            }
+        };
+        report.entities += 1;
+        ids.insert(entity.id, entity_id);
+        existing_ids.insert(entity.id, existing_id);
+    }
+
+    // Every id is known by now, so snippets can link to entities further down the document
+    for entity in &document.entities {
+        let (aliases, snippets) = (report.aliases, report.snippets);
+        import_entity(&tx, entity, existing_ids[&entity.id], &ids, &mut report)?;
+        let added = format!(
+            "{} aliases and {} snippets",
+            report.aliases - aliases,
+            report.snippets - snippets
+        );
+        report.planned.push(match existing_ids[&entity.id] {
+            Some(entity_id) => format!(
+                "{} goes into entity_id {}, {} added",
+                entity_name(entity),
+                entity_id,
+                added
+            ),
+            None => format!(
+                "{} becomes a new entity with {}",
+                entity_name(entity),
+                added
+            ),
+        });
+    }
+    let names: HashMap<u32, String> = document
+        .entities
+        .iter()
+        .map(|entity| (entity.id, entity_name(entity)))
+        .collect();
+    for entity in &document.entities {
+        for relation in &entity.relations {
+            import_relation(&tx, entity, relation, &ids, &names, &mut report)?;
+        }
+    }
+    // Dropping the transaction rolls everything back
+    if !dry_run {
+        tx.commit()?;
+    }
+
+    Ok(report)
+}
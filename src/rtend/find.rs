@@ -1,9 +1,11 @@
 use clap::ArgMatches;
-use rusqlite::{self, params, Connection};
+use rusqlite::{self, params, Connection, ToSql};
 use std::{process, str::FromStr};
 
 use crate::item;
 use crate::item::ComfyTable;
+use crate::link;
+use crate::property::{self, Filter};
 
 pub fn find(args: &ArgMatches, conn: Connection) {
     if args.is_present("find_alias") {
@@ -22,7 +24,12 @@ pub fn find(args: &ArgMatches, conn: Connection) {
                 process::exit(1);
             });
 
-        match find_relation(conn, entity_id, args.is_present("verbose")) {
+        match find_relation(
+            conn,
+            entity_id,
+            args.value_of("label"),
+            args.is_present("verbose"),
+        ) {
             Ok(()) => (),
             Err(e) => {
                 eprintln!("Could not find relation, error: {}", e);
@@ -34,7 +41,7 @@ pub fn find(args: &ArgMatches, conn: Connection) {
         match find_snippet(conn, snippet_string) {
             Ok(()) => (),
             Err(e) => {
-                eprintln!("Could not find relation, error: {}", e);
+                eprintln!("Could not find snippet, error: {}", e);
                 process::exit(1);
             }
         }
@@ -43,7 +50,48 @@ pub fn find(args: &ArgMatches, conn: Connection) {
         match find_relation_snippet(conn, snippet_string) {
             Ok(()) => (),
             Err(e) => {
-                eprintln!("Could not find relation, error: {}", e);
+                eprintln!("Could not find relation snippet, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("find_property") {
+        let filters: Vec<Filter> = args
+            .values_of("find_property")
+            .unwrap()
+            .map(|filter| {
+                Filter::from_str(filter).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    process::exit(1);
+                })
+            })
+            .collect();
+
+        match property::find_property(conn, &filters) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not find property, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("find_links_to") {
+        let entity_id =
+            u32::from_str(args.value_of("find_links_to").unwrap()).unwrap_or_else(|_err| {
+                eprintln!("entity_id must be an u32");
+                process::exit(1);
+            });
+        match link::find_links_to(conn, entity_id) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not find links, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("find_tag") {
+        let tags: Vec<_> = args.values_of("find_tag").unwrap().collect();
+        match find_tag(conn, &tags, args.is_present("any")) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not find tag, error: {}", e);
                 process::exit(1);
             }
         }
@@ -53,7 +101,8 @@ pub fn find(args: &ArgMatches, conn: Connection) {
 fn find_alias(conn: Connection, name: &str, verbose: bool) -> rusqlite::Result<()> {
     if !verbose {
         let mut stmt = conn.prepare(
-            "SELECT id, name, entity_id, updated from alias where name like '%' || ? || '%' order by name",
+            "SELECT id, name, entity_id, updated from alias
+            where name like '%' || ? || '%' and trash_id is null order by name",
         )?;
 
         let entity_iter = stmt.query_map(params![name], |row| {
@@ -74,8 +123,8 @@ fn find_alias(conn: Connection, name: &str, verbose: bool) -> rusqlite::Result<(
     } else {
         let mut stmt = conn.prepare(
             "SELECT a.id, a.name, a.entity_id, (SELECT group_concat(b.name, '; ') from alias b
-            where a.entity_id = b.entity_id and a.id != b.id) as other_alias, a.updated from alias a
-            where a.name like '%' || ? || '%' order by a.name",
+            where a.entity_id = b.entity_id and a.id != b.id and b.trash_id is null) as other_alias,
+            a.updated from alias a where a.name like '%' || ? || '%' and a.trash_id is null order by a.name",
         )?;
 
         let entity_iter = stmt.query_map(params![name], |row| {
@@ -99,19 +148,28 @@ fn find_alias(conn: Connection, name: &str, verbose: bool) -> rusqlite::Result<(
     Ok(())
 }
 
-fn find_relation(conn: Connection, entity_id: u32, verbose: bool) -> rusqlite::Result<()> {
+// Relations are seen from the side of `entity_id`, which is always shown as entity A
+fn find_relation(
+    conn: Connection,
+    entity_id: u32,
+    label: Option<&str>,
+    verbose: bool,
+) -> rusqlite::Result<()> {
     if !verbose {
         let mut stmt = conn.prepare(
-            "SELECT id, entity_id_a, entity_id_b,
-            updated from relation where (entity_id_a = (?1) or entity_id_b = (?1))",
+            "SELECT id, entity_id, label, other_entity_id,
+            updated from directed_relation where entity_id = (?1)
+            and ((?2) is null or relation_type_id in
+            (SELECT id from relation_type where label = (?2) or inverse_label = (?2)))",
         )?;
 
-        let relation_iter = stmt.query_map(params![entity_id], |row| {
+        let relation_iter = stmt.query_map(params![entity_id, label], |row| {
             Ok(item::Relation {
                 id: row.get(0)?,
                 entity_id_a: row.get(1)?,
-                entity_id_b: row.get(2)?,
-                updated: row.get(3)?,
+                label: row.get(2)?,
+                entity_id_b: row.get(3)?,
+                updated: row.get(4)?,
             })
         })?;
 
@@ -124,19 +182,23 @@ fn find_relation(conn: Connection, entity_id: u32, verbose: bool) -> rusqlite::R
     } else {
         let mut stmt = conn.prepare(
             "SELECT id,
-            entity_id_a, (SELECT group_concat(name, '; ') from alias where entity_id = entity_id_a limit 4) as alias_list_a,
-            entity_id_b, (SELECT group_concat(name, '; ') from alias where entity_id = entity_id_b limit 4) as alias_list_b,
-            updated from relation where (entity_id_a = (?1) or entity_id_b = (?1))",
+            entity_id, (SELECT group_concat(name, '; ') from alias where entity_id = directed_relation.entity_id and trash_id is null limit 4) as alias_list_a,
+            label,
+            other_entity_id, (SELECT group_concat(name, '; ') from alias where entity_id = other_entity_id and trash_id is null limit 4) as alias_list_b,
+            updated from directed_relation where entity_id = (?1)
+            and ((?2) is null or relation_type_id in
+            (SELECT id from relation_type where label = (?2) or inverse_label = (?2)))",
         )?;
 
-        let relation_iter = stmt.query_map(params![entity_id], |row| {
+        let relation_iter = stmt.query_map(params![entity_id, label], |row| {
             Ok(item::RelationLong {
                 id: row.get(0)?,
                 entity_id_a: row.get(1)?,
                 alias_list_a: row.get(2)?,
-                entity_id_b: row.get(3)?,
-                alias_list_b: row.get(4)?,
-                updated: row.get(5)?,
+                label: row.get(3)?,
+                entity_id_b: row.get(4)?,
+                alias_list_b: row.get(5)?,
+                updated: row.get(6)?,
             })
         })?;
 
@@ -151,12 +213,16 @@ fn find_relation(conn: Connection, entity_id: u32, verbose: bool) -> rusqlite::R
     Ok(())
 }
 
-fn find_snippet(conn: Connection, string: &str) -> rusqlite::Result<()> {
+// The query is passed as is to FTS5, so phrases ("a b"), prefixes (ab*) and
+// boolean operators (AND, OR, NOT) all work. Best matches come first
+fn find_snippet(conn: Connection, query: &str) -> rusqlite::Result<()> {
     let mut stmt = conn.prepare(
-        "SELECT id, data as snippet, entity_id, updated from snippet where data like '%' || ? || '%'",
+        "SELECT s.id, snippet(snippet_fts, 0, '**', '**', '...', 16) as excerpt, s.entity_id, s.updated
+        from snippet_fts join snippet s on s.id = snippet_fts.rowid
+        where snippet_fts match (?) and s.trash_id is null order by bm25(snippet_fts)",
     )?;
 
-    let snippet_iter = stmt.query_map(params![string], |row| {
+    let snippet_iter = stmt.query_map(params![query], |row| {
         Ok(item::SnippetFound {
             id: row.get(0)?,
             data: row.get(1)?,
@@ -167,7 +233,8 @@ fn find_snippet(conn: Connection, string: &str) -> rusqlite::Result<()> {
 
     let mut tmp_vec = Vec::new();
     for snippet in snippet_iter {
-        tmp_vec.push(snippet.unwrap());
+        // Malformed queries only error out once rows are fetched
+        tmp_vec.push(snippet?);
     }
     let cmfs = item::ComfyStruct { data: tmp_vec };
     cmfs.print_comfy_table();
@@ -175,12 +242,14 @@ fn find_snippet(conn: Connection, string: &str) -> rusqlite::Result<()> {
     Ok(())
 }
 
-fn find_relation_snippet(conn: Connection, string: &str) -> rusqlite::Result<()> {
+fn find_relation_snippet(conn: Connection, query: &str) -> rusqlite::Result<()> {
     let mut stmt = conn.prepare(
-        "SELECT id, data as snippet, relation_id, updated from relation_snippet where data like '%' || ? || '%'",
+        "SELECT s.id, snippet(relation_snippet_fts, 0, '**', '**', '...', 16) as excerpt, s.relation_id, s.updated
+        from relation_snippet_fts join relation_snippet s on s.id = relation_snippet_fts.rowid
+        where relation_snippet_fts match (?) and s.trash_id is null order by bm25(relation_snippet_fts)",
     )?;
 
-    let snippet_iter = stmt.query_map(params![string], |row| {
+    let snippet_iter = stmt.query_map(params![query], |row| {
         Ok(item::RelationSnippetFound {
             id: row.get(0)?,
             data: row.get(1)?,
@@ -191,7 +260,57 @@ fn find_relation_snippet(conn: Connection, string: &str) -> rusqlite::Result<()>
 
     let mut tmp_vec = Vec::new();
     for snippet in snippet_iter {
-        tmp_vec.push(snippet.unwrap());
+        // Malformed queries only error out once rows are fetched
+        tmp_vec.push(snippet?);
+    }
+    let cmfs = item::ComfyStruct { data: tmp_vec };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+fn find_tag(conn: Connection, tags: &[&str], any: bool) -> rusqlite::Result<()> {
+    // Tag names are case insensitive, so are the duplicates
+    let mut tags: Vec<String> = tags.iter().map(|tag| tag.to_lowercase()).collect();
+    tags.sort();
+    tags.dedup();
+
+    // A thing matches if it has at least one of the tags, or all of them
+    let required = if any { 1 } else { tags.len() };
+    let placeholders = vec!["?"; tags.len()].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, 'e' as type,
+        (SELECT group_concat(name, '; ') from alias where entity_id = entity.id and trash_id is null) as data,
+        created as last_modified from entity
+        where trash_id is null and (SELECT count(*) from entity_tag join tag on tag.id = entity_tag.tag_id
+        where entity_tag.entity_id = entity.id and tag.name in ({0})) >= {1}
+        UNION ALL
+        SELECT id, 's', data, updated from snippet
+        where trash_id is null and (SELECT count(*) from snippet_tag join tag on tag.id = snippet_tag.tag_id
+        where snippet_tag.snippet_id = snippet.id and tag.name in ({0})) >= {1}
+        order by 2, 1",
+        placeholders, required
+    ))?;
+
+    // The placeholders are used twice, once for entities and once for snippets
+    let tag_params: Vec<&dyn ToSql> = tags
+        .iter()
+        .chain(tags.iter())
+        .map(|tag| tag as &dyn ToSql)
+        .collect();
+
+    let entity_iter = stmt.query_map(tag_params, |row| {
+        Ok(item::EntityLongLong {
+            id: row.get(0)?,
+            data_type: row.get(1)?,
+            data: row.get(2)?,
+            last_modified: row.get(3)?,
+        })
+    })?;
+
+    let mut tmp_vec = Vec::new();
+    for entity in entity_iter {
+        tmp_vec.push(entity.unwrap());
     }
     let cmfs = item::ComfyStruct { data: tmp_vec };
     cmfs.print_comfy_table();
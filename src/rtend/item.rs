@@ -1,113 +1,427 @@
-use comfy_table::presets::UTF8_FULL;
 use comfy_table::*;
-use time::{Format, OffsetDateTime};
+use serde::Serialize;
+use time::OffsetDateTime;
 
+use crate::config;
+use crate::output::{self, Fields, OutputFormat};
 use crate::utils;
 
+// Timestamps of machine-readable output are always RFC 3339, whatever --time-format says
+mod rfc3339 {
+    use serde::Serializer;
+    use time::{Format, OffsetDateTime};
+
+    pub fn serialize<S: Serializer>(
+        time: &OffsetDateTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&time.format(Format::Rfc3339))
+    }
+}
+
+#[derive(Serialize)]
 pub struct Entity {
     pub id: u32,
+    #[serde(with = "rfc3339")]
     pub created: OffsetDateTime,
 }
 
+impl Fields for Entity {
+    const FIELDS: &'static [&'static str] = &["id", "created"];
+}
+
+#[derive(Serialize)]
 pub struct EntityLong {
     pub id: u32,
     pub alias_list: String,
     pub alias_count: u32,
     pub snippet_count: u32,
+    #[serde(with = "rfc3339")]
     pub created: OffsetDateTime,
 }
 
+impl Fields for EntityLong {
+    const FIELDS: &'static [&'static str] = &[
+        "id",
+        "alias_list",
+        "alias_count",
+        "snippet_count",
+        "created",
+    ];
+}
+
+#[derive(Serialize)]
 pub struct EntityLongLong {
     pub id: u32,
     pub data_type: String,
     pub data: String,
+    #[serde(with = "rfc3339")]
     pub last_modified: OffsetDateTime,
 }
 
+impl Fields for EntityLongLong {
+    const FIELDS: &'static [&'static str] = &["id", "data_type", "data", "last_modified"];
+}
+
+#[derive(Serialize)]
 pub struct Alias {
     pub id: u32,
     pub name: String,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for Alias {
+    const FIELDS: &'static [&'static str] = &["id", "name", "updated"];
+}
+
+#[derive(Serialize)]
 pub struct Snippet {
     pub id: u32,
     pub data: String,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for Snippet {
+    const FIELDS: &'static [&'static str] = &["id", "data", "updated"];
+}
+
+#[derive(Serialize)]
 pub struct Relation {
     pub id: u32,
     pub entity_id_a: u32,
+    pub label: String,
     pub entity_id_b: u32,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for Relation {
+    const FIELDS: &'static [&'static str] =
+        &["id", "entity_id_a", "label", "entity_id_b", "updated"];
+}
+
+#[derive(Serialize)]
 pub struct RelationLong {
     pub id: u32,
     pub entity_id_a: u32,
     pub alias_list_a: String,
+    pub label: String,
     pub entity_id_b: u32,
     pub alias_list_b: String,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for RelationLong {
+    const FIELDS: &'static [&'static str] = &[
+        "id",
+        "entity_id_a",
+        "alias_list_a",
+        "label",
+        "entity_id_b",
+        "alias_list_b",
+        "updated",
+    ];
+}
+
+#[derive(Serialize)]
 pub struct RelationSnippet {
     pub id: u32,
     pub data: String,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for RelationSnippet {
+    const FIELDS: &'static [&'static str] = &["id", "data", "updated"];
+}
+
+#[derive(Serialize)]
 pub struct EntityFound {
     pub id: u32,
     pub name: String,
     pub entity_id: u32,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for EntityFound {
+    const FIELDS: &'static [&'static str] = &["id", "name", "entity_id", "updated"];
+}
+
+#[derive(Serialize)]
 pub struct EntityFoundLong {
     pub id: u32,
     pub name: String,
     pub entity_id: u32,
     pub other_alias: String,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for EntityFoundLong {
+    const FIELDS: &'static [&'static str] = &["id", "name", "entity_id", "other_alias", "updated"];
+}
+
+#[derive(Serialize)]
 pub struct SnippetFound {
     pub id: u32,
     pub data: String,
     pub entity_id: u32,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for SnippetFound {
+    const FIELDS: &'static [&'static str] = &["id", "data", "entity_id", "updated"];
+}
+
+#[derive(Serialize)]
 pub struct RelationSnippetFound {
     pub id: u32,
     pub data: String,
     pub relation_id: u32,
+    #[serde(with = "rfc3339")]
+    pub updated: OffsetDateTime,
+}
+
+impl Fields for RelationSnippetFound {
+    const FIELDS: &'static [&'static str] = &["id", "data", "relation_id", "updated"];
+}
+
+#[derive(Serialize)]
+pub struct Tag {
+    pub id: u32,
+    pub name: String,
+    pub entity_count: u32,
+    pub snippet_count: u32,
+    #[serde(with = "rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+impl Fields for Tag {
+    const FIELDS: &'static [&'static str] =
+        &["id", "name", "entity_count", "snippet_count", "created"];
+}
+
+#[derive(Serialize)]
+pub struct Revision {
+    pub revision: u32,
+    pub data: String,
+    #[serde(with = "rfc3339")]
+    pub created: OffsetDateTime,
+    pub current: bool,
+}
+
+impl Fields for Revision {
+    const FIELDS: &'static [&'static str] = &["revision", "data", "created", "current"];
+}
+
+#[derive(Serialize)]
+pub struct Attachment {
+    pub id: u32,
+    pub entity_id: u32,
+    pub name: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub hash: String,
+    #[serde(with = "rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+impl Fields for Attachment {
+    const FIELDS: &'static [&'static str] = &[
+        "id",
+        "entity_id",
+        "name",
+        "mime_type",
+        "size",
+        "hash",
+        "created",
+    ];
+}
+
+#[derive(Serialize)]
+pub struct Property {
+    pub id: u32,
+    pub key: String,
+    pub value: String,
+    pub property_type: String,
+    #[serde(with = "rfc3339")]
     pub updated: OffsetDateTime,
 }
 
+impl Fields for Property {
+    const FIELDS: &'static [&'static str] = &["id", "key", "value", "property_type", "updated"];
+}
+
+#[derive(Serialize)]
+pub struct PropertyFound {
+    pub entity_id: u32,
+    pub alias_list: String,
+    pub property_list: String,
+    #[serde(with = "rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+impl Fields for PropertyFound {
+    const FIELDS: &'static [&'static str] =
+        &["entity_id", "alias_list", "property_list", "created"];
+}
+
+#[derive(Serialize)]
+pub struct AgendaItem {
+    pub data_type: String,
+    pub id: u32,
+    pub data: String,
+    pub entity_ids: String,
+    pub alias_list: String,
+    pub due: String,
+}
+
+impl Fields for AgendaItem {
+    const FIELDS: &'static [&'static str] =
+        &["data_type", "id", "data", "entity_ids", "alias_list", "due"];
+}
+
+#[derive(Serialize)]
+pub struct Backlink {
+    pub data_type: String,
+    pub id: u32,
+    pub data: String,
+    pub entity_ids: String,
+    pub alias_list: String,
+}
+
+impl Fields for Backlink {
+    const FIELDS: &'static [&'static str] =
+        &["data_type", "id", "data", "entity_ids", "alias_list"];
+}
+
+#[derive(Serialize)]
+pub struct BrokenLink {
+    pub data_type: String,
+    pub id: u32,
+    pub target: String,
+    pub reason: String,
+}
+
+impl Fields for BrokenLink {
+    const FIELDS: &'static [&'static str] = &["data_type", "id", "target", "reason"];
+}
+
+#[derive(Serialize)]
+pub struct JournalEntry {
+    pub id: u32,
+    pub command: String,
+    pub user: String,
+    pub operation: String,
+    pub kind: String,
+    pub row_id: u32,
+    pub old_value: String,
+    pub new_value: String,
+    #[serde(with = "rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+impl Fields for JournalEntry {
+    const FIELDS: &'static [&'static str] = &[
+        "id",
+        "command",
+        "user",
+        "operation",
+        "kind",
+        "row_id",
+        "old_value",
+        "new_value",
+        "created",
+    ];
+}
+
+#[derive(Serialize)]
+pub struct TrashEntry {
+    pub id: u32,
+    pub kind: String,
+    pub item_id: u32,
+    pub summary: String,
+    pub item_count: u32,
+    #[serde(with = "rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+impl Fields for TrashEntry {
+    const FIELDS: &'static [&'static str] =
+        &["id", "kind", "item_id", "summary", "item_count", "created"];
+}
+
+#[derive(Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub default: bool,
+    pub encrypted: bool,
+    pub size: u64,
+    pub entity_count: Option<u32>,
+    #[serde(with = "rfc3339")]
+    pub modified: OffsetDateTime,
+}
+
+impl Fields for Profile {
+    const FIELDS: &'static [&'static str] = &[
+        "name",
+        "default",
+        "encrypted",
+        "size",
+        "entity_count",
+        "modified",
+    ];
+}
+
+#[derive(Serialize)]
 pub struct Stats {
     pub stat_type: String,
     pub count: u32,
 }
 
+impl Fields for Stats {
+    const FIELDS: &'static [&'static str] = &["stat_type", "count"];
+}
+
 pub struct ComfyStruct<T> {
     pub data: Vec<T>,
 }
 
-pub trait ComfyTable {
-    fn print_comfy_table(&self);
+// Rows in the machine-readable formats, with the field names of the structs above
+pub trait Records {
+    fn print_records(&self, format: OutputFormat);
 }
 
-impl ComfyTable for ComfyStruct<Entity> {
+impl<T: Serialize + Fields> Records for ComfyStruct<T> {
+    fn print_records(&self, format: OutputFormat) {
+        output::print_records(&self.data, format);
+    }
+}
+
+pub trait ComfyTable: Records {
+    fn draw_comfy_table(&self);
+
     fn print_comfy_table(&self) {
+        match config::output_format() {
+            format if format.draws_tables() => self.draw_comfy_table(),
+            format => self.print_records(format),
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<Entity> {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![Cell::new("ID"), Cell::new("Created on")]);
@@ -115,7 +429,7 @@ impl ComfyTable for ComfyStruct<Entity> {
             for entity in &self.data {
                 table.add_row(vec![
                     Cell::new(&entity.id),
-                    Cell::new(&entity.created.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.created)),
                 ]);
             }
 
@@ -125,13 +439,13 @@ impl ComfyTable for ComfyStruct<Entity> {
 }
 
 impl ComfyTable for ComfyStruct<EntityLong> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         let mut table = Table::new();
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -148,7 +462,7 @@ impl ComfyTable for ComfyStruct<EntityLong> {
                     Cell::new(&entity.alias_list),
                     Cell::new(&entity.alias_count),
                     Cell::new(&entity.snippet_count),
-                    Cell::new(&entity.created.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.created)),
                 ]);
             }
 
@@ -158,13 +472,13 @@ impl ComfyTable for ComfyStruct<EntityLong> {
 }
 
 impl ComfyTable for ComfyStruct<EntityLongLong> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -179,7 +493,7 @@ impl ComfyTable for ComfyStruct<EntityLongLong> {
                     Cell::new(&entity.id),
                     Cell::new(&entity.data_type),
                     Cell::new(&entity.data),
-                    Cell::new(&entity.last_modified.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.last_modified)),
                 ]);
             }
 
@@ -189,13 +503,13 @@ impl ComfyTable for ComfyStruct<EntityLongLong> {
 }
 
 impl ComfyTable for ComfyStruct<Alias> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -208,7 +522,7 @@ impl ComfyTable for ComfyStruct<Alias> {
                 table.add_row(vec![
                     Cell::new(&entity.name),
                     Cell::new(&entity.id),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
                 ]);
             }
 
@@ -218,13 +532,13 @@ impl ComfyTable for ComfyStruct<Alias> {
 }
 
 impl ComfyTable for ComfyStruct<Snippet> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -237,7 +551,7 @@ impl ComfyTable for ComfyStruct<Snippet> {
                 table.add_row(vec![
                     Cell::new(&snippet.data),
                     Cell::new(&snippet.id),
-                    Cell::new(&snippet.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(snippet.updated)),
                 ]);
             }
 
@@ -247,18 +561,19 @@ impl ComfyTable for ComfyStruct<Snippet> {
 }
 
 impl ComfyTable for ComfyStruct<Relation> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
                     Cell::new("ID"),
                     Cell::new("Entity ID A"),
+                    Cell::new("Label"),
                     Cell::new("Entity ID B"),
                     Cell::new("Last modified"),
                 ]);
@@ -267,8 +582,9 @@ impl ComfyTable for ComfyStruct<Relation> {
                 table.add_row(vec![
                     Cell::new(&entity.id),
                     Cell::new(&entity.entity_id_a),
+                    Cell::new(&entity.label),
                     Cell::new(&entity.entity_id_b),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
                 ]);
             }
 
@@ -278,19 +594,20 @@ impl ComfyTable for ComfyStruct<Relation> {
 }
 
 impl ComfyTable for ComfyStruct<RelationLong> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
                     Cell::new("ID"),
                     Cell::new("ID A"),
                     Cell::new("Alias List A"),
+                    Cell::new("Label"),
                     Cell::new("ID B"),
                     Cell::new("Alias List B"),
                     Cell::new("Last modified"),
@@ -301,9 +618,10 @@ impl ComfyTable for ComfyStruct<RelationLong> {
                     Cell::new(&entity.id),
                     Cell::new(&entity.entity_id_a),
                     Cell::new(&entity.alias_list_a),
+                    Cell::new(&entity.label),
                     Cell::new(&entity.entity_id_b),
                     Cell::new(&entity.alias_list_b),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
                 ]);
             }
 
@@ -313,13 +631,13 @@ impl ComfyTable for ComfyStruct<RelationLong> {
 }
 
 impl ComfyTable for ComfyStruct<RelationSnippet> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -332,7 +650,7 @@ impl ComfyTable for ComfyStruct<RelationSnippet> {
                 table.add_row(vec![
                     Cell::new(&entity.data),
                     Cell::new(&entity.id),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
                 ]);
             }
 
@@ -342,13 +660,13 @@ impl ComfyTable for ComfyStruct<RelationSnippet> {
 }
 
 impl ComfyTable for ComfyStruct<EntityFound> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -363,7 +681,7 @@ impl ComfyTable for ComfyStruct<EntityFound> {
                     Cell::new(&entity.name),
                     Cell::new(&entity.id),
                     Cell::new(&entity.entity_id),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
                 ]);
             }
 
@@ -373,13 +691,13 @@ impl ComfyTable for ComfyStruct<EntityFound> {
 }
 
 impl ComfyTable for ComfyStruct<EntityFoundLong> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -396,7 +714,7 @@ impl ComfyTable for ComfyStruct<EntityFoundLong> {
                     Cell::new(&entity.id),
                     Cell::new(&entity.entity_id),
                     Cell::new(&entity.other_alias),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
                 ]);
             }
 
@@ -406,13 +724,13 @@ impl ComfyTable for ComfyStruct<EntityFoundLong> {
 }
 
 impl ComfyTable for ComfyStruct<SnippetFound> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -427,7 +745,7 @@ impl ComfyTable for ComfyStruct<SnippetFound> {
                     Cell::new(&entity.data),
                     Cell::new(&entity.id),
                     Cell::new(&entity.entity_id),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
                 ]);
             }
 
@@ -437,13 +755,13 @@ impl ComfyTable for ComfyStruct<SnippetFound> {
 }
 
 impl ComfyTable for ComfyStruct<RelationSnippetFound> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![
@@ -458,7 +776,74 @@ impl ComfyTable for ComfyStruct<RelationSnippetFound> {
                     Cell::new(&entity.data),
                     Cell::new(&entity.id),
                     Cell::new(&entity.relation_id),
-                    Cell::new(&entity.updated.format(Format::Rfc3339)),
+                    Cell::new(&config::format_time(entity.updated)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<Tag> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Tag"),
+                    Cell::new("ID"),
+                    Cell::new("Entities"),
+                    Cell::new("Snippets"),
+                    Cell::new("Created on"),
+                ]);
+
+            for tag in &self.data {
+                table.add_row(vec![
+                    Cell::new(&tag.name),
+                    Cell::new(&tag.id),
+                    Cell::new(&tag.entity_count),
+                    Cell::new(&tag.snippet_count),
+                    Cell::new(&config::format_time(tag.created)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<Revision> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Revision"),
+                    Cell::new("Data"),
+                    Cell::new("Last modified"),
+                ]);
+
+            for revision in &self.data {
+                let revision_cell = if revision.current {
+                    Cell::new(format!("{} (current)", revision.revision))
+                } else {
+                    Cell::new(&revision.revision)
+                };
+                table.add_row(vec![
+                    revision_cell,
+                    Cell::new(&revision.data),
+                    Cell::new(&config::format_time(revision.created)),
                 ]);
             }
 
@@ -468,13 +853,13 @@ impl ComfyTable for ComfyStruct<RelationSnippetFound> {
 }
 
 impl ComfyTable for ComfyStruct<Stats> {
-    fn print_comfy_table(&self) {
+    fn draw_comfy_table(&self) {
         if self.data.is_empty() {
             println!("Found nothing.");
         } else {
             let mut table = Table::new();
             table
-                .load_preset(UTF8_FULL)
+                .load_preset(config::table_preset())
                 .set_content_arrangement(ContentArrangement::Dynamic)
                 .set_table_width(utils::get_term_width())
                 .set_header(vec![Cell::new("Type"), Cell::new("Count")]);
@@ -487,3 +872,312 @@ impl ComfyTable for ComfyStruct<Stats> {
         }
     }
 }
+
+impl ComfyTable for ComfyStruct<Attachment> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("ID"),
+                    Cell::new("Entity ID"),
+                    Cell::new("Name"),
+                    Cell::new("Type"),
+                    Cell::new("Size"),
+                    Cell::new("SHA-256"),
+                    Cell::new("Added on"),
+                ]);
+
+            for attachment in &self.data {
+                table.add_row(vec![
+                    Cell::new(&attachment.id),
+                    Cell::new(&attachment.entity_id),
+                    Cell::new(&attachment.name),
+                    Cell::new(&attachment.mime_type),
+                    Cell::new(&attachment.size),
+                    Cell::new(&attachment.hash),
+                    Cell::new(&config::format_time(attachment.created)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<TrashEntry> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Kind"),
+                    Cell::new("ID"),
+                    Cell::new("Content"),
+                    Cell::new("Things"),
+                    Cell::new("Deleted on"),
+                ]);
+
+            for entry in &self.data {
+                table.add_row(vec![
+                    Cell::new(&entry.kind),
+                    Cell::new(&entry.item_id),
+                    Cell::new(&entry.summary),
+                    Cell::new(&entry.item_count),
+                    Cell::new(&config::format_time(entry.created)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<Profile> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Profile"),
+                    Cell::new("Default"),
+                    Cell::new("Size"),
+                    Cell::new("Entities"),
+                    Cell::new("Last modified"),
+                ]);
+
+            for profile in &self.data {
+                table.add_row(vec![
+                    Cell::new(&profile.name),
+                    Cell::new(if profile.default { "*" } else { "" }),
+                    Cell::new(&profile.size),
+                    match (profile.entity_count, profile.encrypted) {
+                        (_, true) => Cell::new("encrypted"),
+                        (Some(count), false) => Cell::new(&count),
+                        (None, false) => Cell::new("unreadable"),
+                    },
+                    Cell::new(&config::format_time(profile.modified)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<Property> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("ID"),
+                    Cell::new("Key"),
+                    Cell::new("Value"),
+                    Cell::new("Type"),
+                    Cell::new("Last modified"),
+                ]);
+
+            for property in &self.data {
+                table.add_row(vec![
+                    Cell::new(&property.id),
+                    Cell::new(&property.key),
+                    Cell::new(&property.value),
+                    Cell::new(&property.property_type),
+                    Cell::new(&config::format_time(property.updated)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<PropertyFound> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Entity ID"),
+                    Cell::new("Alias List"),
+                    Cell::new("Properties"),
+                    Cell::new("Created on"),
+                ]);
+
+            for entity in &self.data {
+                table.add_row(vec![
+                    Cell::new(&entity.entity_id),
+                    Cell::new(&entity.alias_list),
+                    Cell::new(&entity.property_list),
+                    Cell::new(&config::format_time(entity.created)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<AgendaItem> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Type"),
+                    Cell::new("ID"),
+                    Cell::new("Data"),
+                    Cell::new("Entity ID"),
+                    Cell::new("Alias List"),
+                ]);
+
+            for entity in &self.data {
+                table.add_row(vec![
+                    Cell::new(&entity.data_type),
+                    Cell::new(&entity.id),
+                    Cell::new(&entity.data),
+                    Cell::new(&entity.entity_ids),
+                    Cell::new(&entity.alias_list),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<Backlink> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Type"),
+                    Cell::new("ID"),
+                    Cell::new("Data"),
+                    Cell::new("Entity ID"),
+                    Cell::new("Alias List"),
+                ]);
+
+            for entity in &self.data {
+                table.add_row(vec![
+                    Cell::new(&entity.data_type),
+                    Cell::new(&entity.id),
+                    Cell::new(&entity.data),
+                    Cell::new(&entity.entity_ids),
+                    Cell::new(&entity.alias_list),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<BrokenLink> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("Type"),
+                    Cell::new("ID"),
+                    Cell::new("Link"),
+                    Cell::new("Reason"),
+                ]);
+
+            for entity in &self.data {
+                table.add_row(vec![
+                    Cell::new(&entity.data_type),
+                    Cell::new(&entity.id),
+                    Cell::new(&format!("[[{}]]", entity.target)),
+                    Cell::new(&entity.reason),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
+
+impl ComfyTable for ComfyStruct<JournalEntry> {
+    fn draw_comfy_table(&self) {
+        if self.data.is_empty() {
+            println!("Found nothing.");
+        } else {
+            let mut table = Table::new();
+            table
+                .load_preset(config::table_preset())
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_table_width(utils::get_term_width())
+                .set_header(vec![
+                    Cell::new("ID"),
+                    Cell::new("Command"),
+                    Cell::new("User"),
+                    Cell::new("Operation"),
+                    Cell::new("Kind"),
+                    Cell::new("Row ID"),
+                    Cell::new("Old"),
+                    Cell::new("New"),
+                    Cell::new("Changed on"),
+                ]);
+
+            for entry in &self.data {
+                table.add_row(vec![
+                    Cell::new(&entry.id),
+                    Cell::new(&entry.command),
+                    Cell::new(&entry.user),
+                    Cell::new(&entry.operation),
+                    Cell::new(&entry.kind),
+                    Cell::new(&entry.row_id),
+                    Cell::new(&entry.old_value),
+                    Cell::new(&entry.new_value),
+                    Cell::new(&config::format_time(entry.created)),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+}
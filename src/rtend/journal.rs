@@ -0,0 +1,185 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, types::Value, Connection, ToSql};
+use std::{env, process, str::FromStr};
+use time::Date;
+
+use crate::config;
+use crate::item;
+use crate::item::ComfyTable;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// An update only shows the fields that changed
+const CHANGED_FIELDS: &str = "(SELECT json_group_object(old.key, {0}.value)
+    from json_each(journal.old_value) old join json_each(journal.new_value) new
+    on new.key = old.key where old.value is not new.value)";
+
+// Tables the journal triggers are on
+pub const KINDS: &[&str] = &[
+    "entity",
+    "alias",
+    "snippet",
+    "relation",
+    "relation_snippet",
+    "attachment",
+    "property",
+    "tag",
+    "entity_tag",
+    "snippet_tag",
+    "relation_type",
+    "trash",
+    "snippet_revision",
+    "relation_snippet_revision",
+    "review",
+];
+
+// Subcommands whose changes end up in the journal
+pub const JOURNALED_COMMANDS: &[&str] = &[
+    "add", "delete", "edit", "import", "merge", "review", "trash",
+];
+
+// Who the changes are recorded for, the `user_name` of the config or else the OS user
+pub fn user() -> Option<String> {
+    config::get()
+        .user_name
+        .clone()
+        .or_else(|| env::var("USER").ok())
+        .or_else(|| env::var("USERNAME").ok())
+        .filter(|user| !user.is_empty())
+}
+
+// Files the following changes under the command line, the journal triggers pick the latest
+pub fn begin(conn: &Connection) -> rusqlite::Result<()> {
+    let command = env::args()
+        .skip(1)
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("{:?}", arg)
+            } else {
+                arg
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    conn.execute(
+        "INSERT INTO journal_command (command, user) VALUES (?1, ?2)",
+        params![command, user()],
+    )?;
+
+    Ok(())
+}
+
+fn get_date(args: &ArgMatches, name: &str) -> Option<String> {
+    args.value_of(name).map(|date| {
+        Date::parse(date, DATE_FORMAT)
+            .map(|date| date.format(DATE_FORMAT))
+            .unwrap_or_else(|_err| {
+                eprintln!("{} must be a date like 2021-01-31", name);
+                process::exit(1);
+            })
+    })
+}
+
+pub fn log(args: &ArgMatches, conn: Connection) {
+    let entity_id = args.value_of("entity").map(|entity_id| {
+        u32::from_str(entity_id).unwrap_or_else(|_err| {
+            eprintln!("entity_id must be an u32");
+            process::exit(1);
+        })
+    });
+    let limit = u32::from_str(args.value_of("limit").unwrap()).unwrap_or_else(|_err| {
+        eprintln!("count must be an u32");
+        process::exit(1);
+    });
+
+    match list_journal(
+        conn,
+        entity_id,
+        args.value_of("kind"),
+        get_date(args, "since"),
+        get_date(args, "until"),
+        limit,
+    ) {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("Could not show the journal, error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// The latest changes first, `since` and `until` are local dates and both included
+fn list_journal(
+    conn: Connection,
+    entity_id: Option<u32>,
+    kind: Option<&str>,
+    since: Option<String>,
+    until: Option<String>,
+    limit: u32,
+) -> rusqlite::Result<()> {
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    if let Some(entity_id) = entity_id {
+        conditions.push("(journal.entity_id = ? or journal.other_entity_id = ?)");
+        values.push(Value::Integer(entity_id.into()));
+        values.push(Value::Integer(entity_id.into()));
+    }
+    if let Some(kind) = kind {
+        conditions.push("journal.kind = ?");
+        values.push(Value::Text(kind.to_string()));
+    }
+    if let Some(since) = since {
+        conditions.push("date(journal.created, 'localtime') >= ?");
+        values.push(Value::Text(since));
+    }
+    if let Some(until) = until {
+        conditions.push("date(journal.created, 'localtime') <= ?");
+        values.push(Value::Text(until));
+    }
+    values.push(Value::Integer(limit.into()));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT journal.id,
+        coalesce(CASE journal_command.kind WHEN 'do' THEN '' ELSE journal_command.kind || ' ' END
+        || journal_command.command, ''), coalesce(journal_command.user, ''),
+        journal.operation, journal.kind,
+        journal.row_id,
+        CASE journal.operation WHEN 'update' THEN {} ELSE coalesce(journal.old_value, '') END,
+        CASE journal.operation WHEN 'update' THEN {} ELSE coalesce(journal.new_value, '') END,
+        journal.created
+        from journal left join journal_command on journal_command.id = journal.command_id
+        {} order by journal.id desc limit ?",
+        CHANGED_FIELDS.replace("{0}", "old"),
+        CHANGED_FIELDS.replace("{0}", "new"),
+        if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("where {}", conditions.join(" and "))
+        }
+    ))?;
+    let query_params: Vec<&dyn ToSql> = values.iter().map(|value| value as &dyn ToSql).collect();
+
+    let journal_iter = stmt.query_map(query_params, |row| {
+        Ok(item::JournalEntry {
+            id: row.get(0)?,
+            command: row.get(1)?,
+            user: row.get(2)?,
+            operation: row.get(3)?,
+            kind: row.get(4)?,
+            row_id: row.get(5)?,
+            old_value: row.get(6)?,
+            new_value: row.get(7)?,
+            created: row.get(8)?,
+        })
+    })?;
+
+    let mut tmp_vec = Vec::new();
+    for entry in journal_iter {
+        tmp_vec.push(entry?);
+    }
+    let cmfs = item::ComfyStruct { data: tmp_vec };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
@@ -1,9 +1,28 @@
 pub mod add;
+pub mod agenda;
+pub mod attachment;
+pub mod backup;
+pub mod config;
 pub mod delete;
 pub mod edit;
+pub mod encryption;
+pub mod export;
 pub mod find;
 pub mod item;
+pub mod journal;
+pub mod link;
 pub mod list;
+pub mod markdown;
+pub mod merge;
+pub mod migration;
+pub mod output;
+pub mod profile;
+pub mod property;
+pub mod review;
+pub mod revision;
+pub mod tag;
+pub mod trash;
+pub mod undo;
 pub mod utils;
 
 #[cfg(target_family = "unix")]
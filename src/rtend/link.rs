@@ -0,0 +1,260 @@
+use rusqlite::{self, params, Connection};
+use std::{collections::HashMap, process, str::FromStr};
+
+use crate::config;
+use crate::item;
+use crate::item::ComfyTable;
+use crate::revision::SnippetKind;
+
+// Everything between `[[` and `]]`, an alias or `#<entity_id>`. Links spanning lines or
+// being empty are left as plain text
+pub fn parse_links(data: &str) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    let mut rest = data;
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        let end = match rest.find("]]") {
+            Some(end) => end,
+            None => break,
+        };
+        let target = rest[..end].trim();
+        if !target.is_empty()
+            && !target.contains('\n')
+            && !targets.iter().any(|existing| existing == target)
+        {
+            targets.push(target.to_string());
+        }
+        rest = &rest[end + 2..];
+    }
+
+    targets
+}
+
+// Replaces the target of every link `rewrite` returns a new one for
+pub fn rewrite_links<F>(data: &str, rewrite: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut rewritten = String::with_capacity(data.len());
+    let mut rest = data;
+    while let Some(start) = rest.find("[[") {
+        rewritten.push_str(&rest[..start + 2]);
+        rest = &rest[start + 2..];
+        let end = match rest.find("]]") {
+            Some(end) => end,
+            None => break,
+        };
+        match rewrite(rest[..end].trim()) {
+            Some(target) => rewritten.push_str(&target),
+            None => rewritten.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+    rewritten.push_str(rest);
+
+    rewritten
+}
+
+// The entity id of a `[[#<entity_id>]]` link
+pub fn entity_id(target: &str) -> Option<u32> {
+    target
+        .strip_prefix('#')
+        .and_then(|id| u32::from_str(id).ok())
+}
+
+// Points `[[#<entity_id>]]` links at the new ids of the entities, other links stay as they are
+pub fn remap_ids(data: &str, ids: &HashMap<u32, u32>) -> String {
+    rewrite_links(data, |target| {
+        entity_id(target)
+            .and_then(|id| ids.get(&id))
+            .map(|new_id| format!("#{}", new_id))
+    })
+}
+
+fn link_column(kind: SnippetKind) -> &'static str {
+    match kind {
+        SnippetKind::Snippet => "snippet_id",
+        SnippetKind::RelationSnippet => "relation_snippet_id",
+    }
+}
+
+// Replaces the stored links of a snippet with the ones in its current text
+pub fn update_links(
+    conn: &Connection,
+    kind: SnippetKind,
+    id: u32,
+    data: &str,
+) -> rusqlite::Result<()> {
+    let column = link_column(kind);
+    conn.execute(
+        &format!("DELETE from link where {} = (?)", column),
+        params![id],
+    )?;
+    for target in parse_links(data) {
+        conn.execute(
+            &format!("INSERT INTO link ({}, target) VALUES (?1, ?2)", column),
+            params![id, target],
+        )?;
+    }
+
+    Ok(())
+}
+
+// Parses the links of every snippet from `first_id` on again
+pub fn relink(conn: &Connection, kind: SnippetKind, first_id: u32) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, data from {} where id >= (?)",
+        kind.table()
+    ))?;
+    let snippets = stmt
+        .query_map(params![first_id], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    for (id, data) in snippets {
+        update_links(conn, kind, id, &data)?;
+    }
+
+    Ok(())
+}
+
+// Live snippets and relation snippets linking to the entity
+pub fn list_backlinks(conn: &Connection, entity_id: u32) -> rusqlite::Result<Vec<item::Backlink>> {
+    let mut stmt = conn.prepare(
+        "SELECT 's' as type, snippet.id, snippet.data, cast(snippet.entity_id as text),
+        (SELECT group_concat(name, '; ') from alias where alias.entity_id = snippet.entity_id
+        and trash_id is null) as alias_list
+        from snippet where snippet.trash_id is null and snippet.id in
+        (SELECT snippet_id from link join resolved_link on resolved_link.link_id = link.id
+        where resolved_link.entity_id = (?1))
+        UNION ALL
+        SELECT 'rs', relation_snippet.id, relation_snippet.data, entity_id_a || ' | ' || entity_id_b,
+        coalesce((SELECT group_concat(name, '; ') from alias where alias.entity_id = entity_id_a
+        and trash_id is null), '') || ' | ' ||
+        coalesce((SELECT group_concat(name, '; ') from alias where alias.entity_id = entity_id_b
+        and trash_id is null), '')
+        from relation_snippet join relation on relation.id = relation_snippet.relation_id
+        where relation_snippet.trash_id is null and relation_snippet.id in
+        (SELECT relation_snippet_id from link join resolved_link on resolved_link.link_id = link.id
+        where resolved_link.entity_id = (?1))
+        order by 1 desc, 2",
+    )?;
+
+    let backlink_iter = stmt.query_map(params![entity_id], |row| {
+        Ok(item::Backlink {
+            data_type: row.get(0)?,
+            id: row.get(1)?,
+            data: row.get(2)?,
+            entity_ids: row.get(3)?,
+            alias_list: row.get(4).unwrap_or_else(|_| "".to_string()),
+        })
+    })?;
+
+    backlink_iter.collect()
+}
+
+pub fn find_links_to(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
+    let cmfs = item::ComfyStruct {
+        data: list_backlinks(&conn, entity_id)?,
+    };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+// Links of live snippets which point to no live entity
+fn find_broken_links(conn: &Connection) -> rusqlite::Result<Vec<item::BrokenLink>> {
+    let mut stmt = conn.prepare(
+        "SELECT CASE WHEN link.snippet_id is null THEN 'rs' ELSE 's' END as type,
+        coalesce(link.snippet_id, link.relation_snippet_id), link.target,
+        CASE WHEN link.target glob '#[0-9]*' and
+        (SELECT trash_id from entity where '#' || id = link.target) is not null
+        THEN 'entity is in the trash'
+        WHEN link.target glob '#[0-9]*' THEN 'no such entity'
+        ELSE 'no such alias' END
+        from link
+        left join snippet on snippet.id = link.snippet_id
+        left join relation_snippet on relation_snippet.id = link.relation_snippet_id
+        where coalesce(snippet.trash_id, relation_snippet.trash_id) is null
+        and link.id not in (SELECT link_id from resolved_link)
+        order by 1 desc, 2, 3",
+    )?;
+
+    let broken_link_iter = stmt.query_map(params![], |row| {
+        Ok(item::BrokenLink {
+            data_type: row.get(0)?,
+            id: row.get(1)?,
+            target: row.get(2)?,
+            reason: row.get(3)?,
+        })
+    })?;
+
+    broken_link_iter.collect()
+}
+
+pub fn check(conn: Connection) {
+    match find_broken_links(&conn) {
+        Ok(broken_links) if !config::output_format().draws_tables() => {
+            let cmfs = item::ComfyStruct { data: broken_links };
+            cmfs.print_comfy_table();
+        }
+        Ok(broken_links) if broken_links.is_empty() => println!("All links resolve."),
+        Ok(broken_links) => {
+            println!("{} links could not be resolved", broken_links.len());
+            let cmfs = item::ComfyStruct { data: broken_links };
+            cmfs.print_comfy_table();
+        }
+        Err(e) => {
+            eprintln!("Could not check links, error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entity_id, parse_links, remap_ids, rewrite_links};
+    use std::collections::HashMap;
+
+    #[test]
+    fn links_are_parsed_once_in_order() {
+        assert_eq!(
+            parse_links("met [[Bob]] and [[ #12 ]], then [[Bob]] again"),
+            vec!["Bob".to_string(), "#12".to_string()]
+        );
+    }
+
+    #[test]
+    fn broken_links_are_plain_text() {
+        assert!(parse_links("no links here").is_empty());
+        assert!(parse_links("[[]] [[   ]]").is_empty());
+        assert!(parse_links("[[over\ntwo lines]]").is_empty());
+        assert_eq!(parse_links("[[Bob]] [[unclosed"), vec!["Bob".to_string()]);
+    }
+
+    #[test]
+    fn rewriting_keeps_everything_else() {
+        let rewritten = rewrite_links("[[Bob]], [[ Alice ]] and [[Eve", |target| {
+            if target == "Alice" {
+                Some("#3".to_string())
+            } else {
+                None
+            }
+        });
+        assert_eq!(rewritten, "[[Bob]], [[#3]] and [[Eve");
+    }
+
+    #[test]
+    fn id_links_are_remapped() {
+        assert_eq!(entity_id("#7"), Some(7));
+        assert_eq!(entity_id("7"), None);
+        assert_eq!(entity_id("#x"), None);
+
+        let ids: HashMap<u32, u32> = vec![(1, 10), (2, 20)].into_iter().collect();
+        assert_eq!(
+            remap_ids("[[#1]] [[ #2 ]] [[#3]] [[Bob]]", &ids),
+            "[[#10]] [[#20]] [[#3]] [[Bob]]"
+        );
+    }
+}
@@ -2,8 +2,13 @@ use clap::ArgMatches;
 use rusqlite::{self, params, Connection};
 use std::{process, str::FromStr};
 
+use crate::config;
 use crate::item;
 use crate::item::ComfyTable;
+use crate::link;
+use crate::property;
+use crate::review;
+use crate::revision::{self, SnippetKind};
 
 pub fn list(args: &ArgMatches, conn: Connection) {
     if args.is_present("list_entity") {
@@ -93,17 +98,82 @@ pub fn list(args: &ArgMatches, conn: Connection) {
                 process::exit(1);
             }
         }
+    } else if args.is_present("list_tag") {
+        match list_tag(conn) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not list tags, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("list_attachment") {
+        let entity_id = args.value_of("list_attachment").map(|id| {
+            u32::from_str(id).unwrap_or_else(|_err| {
+                eprintln!("entity_id must be an u32");
+                process::exit(1);
+            })
+        });
+
+        match list_attachment(conn, entity_id) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not list attachments, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("list_history") || args.is_present("list_relation_history") {
+        let (kind, arg) = if args.is_present("list_history") {
+            (SnippetKind::Snippet, "list_history")
+        } else {
+            (SnippetKind::RelationSnippet, "list_relation_history")
+        };
+        let id = u32::from_str(args.value_of(arg).unwrap()).unwrap_or_else(|_err| {
+            eprintln!("snippet_id must be an u32");
+            process::exit(1);
+        });
+
+        match list_history(conn, kind, id) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not list history, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if args.is_present("list_diff") || args.is_present("list_relation_diff") {
+        let (kind, arg) = if args.is_present("list_diff") {
+            (SnippetKind::Snippet, "list_diff")
+        } else {
+            (SnippetKind::RelationSnippet, "list_relation_diff")
+        };
+        let diff_args: Vec<u32> = args
+            .values_of(arg)
+            .unwrap()
+            .map(|value| {
+                u32::from_str(value).unwrap_or_else(|_err| {
+                    eprintln!("snippet_id and revisions must be an u32");
+                    process::exit(1);
+                })
+            })
+            .collect();
+
+        match list_diff(conn, kind, diff_args[0], diff_args[1], diff_args[2]) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not diff revisions, error: {}", e);
+                process::exit(1);
+            }
+        }
     }
 }
 
 fn list_verbose(conn: Connection) -> rusqlite::Result<()> {
     let mut stmt = conn.prepare("
         SELECT id,
-        (SELECT substr(group_concat(name, '; '), 0, 1000) from alias where entity_id = entity.id limit 4) as alias_list,
-        (SELECT count(*) from alias where entity_id = entity.id) as alias_count,
-        (SELECT count(*) from snippet where entity_id = entity.id) as snippet_count,
+        (SELECT substr(group_concat(name, '; '), 0, 1000) from alias where entity_id = entity.id and trash_id is null limit 4) as alias_list,
+        (SELECT count(*) from alias where entity_id = entity.id and trash_id is null) as alias_count,
+        (SELECT count(*) from snippet where entity_id = entity.id and trash_id is null) as snippet_count,
         created
-        from entity order by 1
+        from entity where trash_id is null order by 1
         ")?;
 
     let entity_iter = stmt.query_map(params![], |row| {
@@ -128,7 +198,8 @@ fn list_verbose(conn: Connection) -> rusqlite::Result<()> {
 fn list_entity(conn: Connection, entity_id: u32, verbosity_level: u64) -> rusqlite::Result<()> {
     // No verbosity level, basically just lists the created date
     if verbosity_level == 0 {
-        let mut stmt = conn.prepare("SELECT * from entity where id = (?1)")?;
+        let mut stmt =
+            conn.prepare("SELECT id, created from entity where id = (?1) and trash_id is null")?;
         let entity_iter = stmt.query_map(params![entity_id], |row| {
             Ok(item::Entity {
                 id: row.get(0)?,
@@ -147,11 +218,11 @@ fn list_entity(conn: Connection, entity_id: u32, verbosity_level: u64) -> rusqli
     } else if verbosity_level == 1 {
         let mut stmt = conn.prepare("
         SELECT id,
-        (SELECT substr(group_concat(name, '; '), 0, 1000) from alias where entity_id = entity.id limit 4) as alias_list,
-        (SELECT count(*) from alias where entity_id = entity.id) as alias_count,
-        (SELECT count(*) from snippet where entity_id = entity.id) as snippet_count,
+        (SELECT substr(group_concat(name, '; '), 0, 1000) from alias where entity_id = entity.id and trash_id is null limit 4) as alias_list,
+        (SELECT count(*) from alias where entity_id = entity.id and trash_id is null) as alias_count,
+        (SELECT count(*) from snippet where entity_id = entity.id and trash_id is null) as snippet_count,
         created
-        from entity where id = (?1) order by 1
+        from entity where id = (?1) and trash_id is null order by 1
         ")?;
 
         let entity_iter = stmt.query_map(params![entity_id], |row| {
@@ -173,24 +244,49 @@ fn list_entity(conn: Connection, entity_id: u32, verbosity_level: u64) -> rusqli
 
     // Equal to list entity long long
     } else {
-        let mut stmt = conn.prepare(
+        // Tables show properties and backlinks on their own, machine-readable output has them
+        // as rows of type `p`, and `bs` or `brs` for snippets and relation snippets linking here
+        let machine_readable = !config::output_format().draws_tables();
+        let mut stmt = conn.prepare(&format!(
             "
-            SELECT id, 'e' as type, cast(id as text) as data, created as last_modified from entity where id = (?1)
+            SELECT id, 'e' as type, cast(id as text) as data, created as last_modified from entity
+            where id = (?1) and trash_id is null
+            UNION ALL
+            SELECT id, 'a', name, updated from alias where entity_id = (?1) and trash_id is null
+            UNION ALL
+            SELECT id, 's', data || coalesce(' [due ' || due || ']', ''), updated from snippet
+            where entity_id = (?1) and trash_id is null
+            UNION ALL
+            SELECT id, 'r',
+            (entity_id || ' | ' || (CASE label WHEN '' THEN '' ELSE label || ' | ' END) || other_entity_id) as 'a | b',
+            updated from directed_relation where entity_id = (?1)
+            UNION ALL
+            SELECT id, 'rs', data || coalesce(' [due ' || due || ']', ''), updated from relation_snippet
+            where relation_id in (SELECT id from directed_relation where entity_id = (?1)) and trash_id is null
+            UNION ALL
+            SELECT id, 'f', name || ' (' || mime_type || ')', created from attachment
+            where entity_id = (?1) and trash_id is null
             UNION ALL
-            SELECT id, 'a', name, updated from alias where entity_id = (?1)
+            SELECT tag.id, 't', tag.name, entity_tag.created from entity_tag
+            join tag on tag.id = entity_tag.tag_id where entity_tag.entity_id = (?1)
+            and (?1) in (SELECT id from entity where trash_id is null)
             UNION ALL
-            SELECT id, 's', data, updated from snippet where entity_id = (?1)
+            SELECT id, 'p', key || '=' || {}, updated from property
+            where (?2) and entity_id = (?1) and trash_id is null
             UNION ALL
-            SELECT id, 'r', (entity_id_a || ' | ' || entity_id_b) as 'a | b',
-            updated from relation where (entity_id_a = (?1) or entity_id_b = (?1))
+            SELECT id, 'bs', data, updated from snippet
+            where (?2) and trash_id is null and id in (SELECT snippet_id from link
+            join resolved_link on resolved_link.link_id = link.id where resolved_link.entity_id = (?1))
             UNION ALL
-            SELECT id, 'rs', data, updated from relation_snippet
-            where relation_id in (SELECT id from relation where (entity_id_a = (?1) or entity_id_b = (?1)))
+            SELECT id, 'brs', data, updated from relation_snippet
+            where (?2) and trash_id is null and id in (SELECT relation_snippet_id from link
+            join resolved_link on resolved_link.link_id = link.id where resolved_link.entity_id = (?1))
             order by 2, 1
             ",
-        )?;
+            property::DISPLAY_VALUE
+        ))?;
 
-        let entity_iter = stmt.query_map(params![entity_id], |row| {
+        let entity_iter = stmt.query_map(params![entity_id, machine_readable], |row| {
             Ok(item::EntityLongLong {
                 id: row.get(0)?,
                 data_type: row.get(1)?,
@@ -205,13 +301,33 @@ fn list_entity(conn: Connection, entity_id: u32, verbosity_level: u64) -> rusqli
         }
         let cmfs = item::ComfyStruct { data: tmp_vec };
         cmfs.print_comfy_table();
+
+        if machine_readable {
+            return Ok(());
+        }
+
+        let properties = property::list_properties(&conn, entity_id)?;
+        if !properties.is_empty() {
+            println!("Properties");
+            let cmfs = item::ComfyStruct { data: properties };
+            cmfs.print_comfy_table();
+        }
+
+        let backlinks = link::list_backlinks(&conn, entity_id)?;
+        if !backlinks.is_empty() {
+            println!("Backlinks");
+            let cmfs = item::ComfyStruct { data: backlinks };
+            cmfs.print_comfy_table();
+        }
     }
 
     Ok(())
 }
 
 fn list_alias(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
-    let mut stmt = conn.prepare("SELECT id, name, updated from alias where entity_id = (?)")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, updated from alias where entity_id = (?) and trash_id is null",
+    )?;
 
     let alias_iter = stmt.query_map(params![entity_id], |row| {
         Ok(item::Alias {
@@ -233,7 +349,9 @@ fn list_alias(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
 
 fn list_snippet(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
     let mut stmt =
-        conn.prepare("SELECT id, data as snippet, updated from snippet where entity_id = (?)")?;
+        conn.prepare(
+        "SELECT id, data as snippet, updated from snippet where entity_id = (?) and trash_id is null",
+    )?;
 
     let snippet_iter = stmt.query_map(params![entity_id], |row| {
         Ok(item::Snippet {
@@ -256,16 +374,18 @@ fn list_snippet(conn: Connection, entity_id: u32) -> rusqlite::Result<()> {
 fn list_relation(conn: Connection, relation_id: u32, verbose: bool) -> rusqlite::Result<()> {
     if !verbose {
         let mut stmt = conn.prepare(
-            "SELECT id, entity_id_a, entity_id_b,
-            updated from relation where id = (?)",
+            "SELECT relation.id, entity_id_a, coalesce(relation_type.label, '') as label, entity_id_b,
+            relation.updated from relation left join relation_type on relation_type.id = relation.relation_type_id
+            where relation.id = (?) and relation.trash_id is null",
         )?;
 
         let relation_iter = stmt.query_map(params![relation_id], |row| {
             Ok(item::Relation {
                 id: row.get(0)?,
                 entity_id_a: row.get(1)?,
-                entity_id_b: row.get(2)?,
-                updated: row.get(3)?,
+                label: row.get(2)?,
+                entity_id_b: row.get(3)?,
+                updated: row.get(4)?,
             })
         })?;
 
@@ -277,10 +397,12 @@ fn list_relation(conn: Connection, relation_id: u32, verbose: bool) -> rusqlite:
         cmfs.print_comfy_table();
     } else {
         let mut stmt = conn.prepare(
-            "SELECT id,
-            entity_id_a, (SELECT group_concat(name, '; ') from alias where entity_id = entity_id_a limit 4) as alias_list_a,
-            entity_id_b, (SELECT group_concat(name, '; ') from alias where entity_id = entity_id_b limit 4) as alias_list_b,
-            updated from relation where id = (?)",
+            "SELECT relation.id,
+            entity_id_a, (SELECT group_concat(name, '; ') from alias where entity_id = entity_id_a and trash_id is null limit 4) as alias_list_a,
+            coalesce(relation_type.label, '') as label,
+            entity_id_b, (SELECT group_concat(name, '; ') from alias where entity_id = entity_id_b and trash_id is null limit 4) as alias_list_b,
+            relation.updated from relation left join relation_type on relation_type.id = relation.relation_type_id
+            where relation.id = (?) and relation.trash_id is null",
         )?;
 
         let relation_iter = stmt.query_map(params![relation_id], |row| {
@@ -288,9 +410,10 @@ fn list_relation(conn: Connection, relation_id: u32, verbose: bool) -> rusqlite:
                 id: row.get(0)?,
                 entity_id_a: row.get(1)?,
                 alias_list_a: row.get(2)?,
-                entity_id_b: row.get(3)?,
-                alias_list_b: row.get(4)?,
-                updated: row.get(5)?,
+                label: row.get(3)?,
+                entity_id_b: row.get(4)?,
+                alias_list_b: row.get(5)?,
+                updated: row.get(6)?,
             })
         })?;
 
@@ -307,7 +430,7 @@ fn list_relation(conn: Connection, relation_id: u32, verbose: bool) -> rusqlite:
 
 fn list_relation_snippet(conn: Connection, relation_id: u32) -> rusqlite::Result<()> {
     let mut stmt = conn.prepare(
-        "SELECT id, data as snippet, updated from relation_snippet where relation_id = (?)",
+        "SELECT id, data as snippet, updated from relation_snippet where relation_id = (?) and trash_id is null",
     )?;
 
     let relation_iter = stmt.query_map(params![relation_id], |row| {
@@ -330,18 +453,34 @@ fn list_relation_snippet(conn: Connection, relation_id: u32) -> rusqlite::Result
 
 fn list_stats(conn: Connection) -> rusqlite::Result<()> {
     let mut stmt = conn.prepare(
-        "SELECT 'Entities', count(*) from entity
+        "SELECT 'Entities', count(*) from entity where trash_id is null
+        UNION ALL
+        SELECT 'Aliases', count(*) from alias where trash_id is null
+        UNION ALL
+        SELECT 'Snippets', count(*) from snippet where trash_id is null
+        UNION ALL
+        SELECT 'Relations', count(*) from relation where trash_id is null
+        UNION ALL
+        SELECT 'Relation Snippets', count(*) from relation_snippet where trash_id is null
+        UNION ALL
+        SELECT 'Attachments', count(*) from attachment where trash_id is null
         UNION ALL
-        SELECT 'Aliases', count(*) from alias
+        SELECT 'Properties', count(*) from property where trash_id is null
         UNION ALL
-        SELECT 'Snippets', count(*) from snippet
+        SELECT 'Tags', count(*) from tag
         UNION ALL
-        SELECT 'Relations', count(*) from relation
+        SELECT 'Tagged Entities', count(distinct entity_id) from entity_tag
+        where entity_id in (SELECT id from entity where trash_id is null)
         UNION ALL
-        SELECT 'Relation Snippets', count(*) from relation_snippet",
+        SELECT 'Tagged Snippets', count(distinct snippet_id) from snippet_tag
+        where snippet_id in (SELECT id from snippet where trash_id is null)
+        UNION ALL
+        SELECT 'Due for review', (?1)
+        UNION ALL
+        SELECT 'Trash', count(*) from trash",
     )?;
 
-    let stat_iter = stmt.query_map(params![], |row| {
+    let stat_iter = stmt.query_map(params![review::count_due(&conn)?], |row| {
         Ok(item::Stats {
             stat_type: row.get(0)?,
             count: row.get(1)?,
@@ -357,3 +496,103 @@ fn list_stats(conn: Connection) -> rusqlite::Result<()> {
 
     Ok(())
 }
+
+fn list_tag(conn: Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name,
+        (SELECT count(*) from entity_tag where tag_id = tag.id
+        and entity_id in (SELECT id from entity where trash_id is null)) as entity_count,
+        (SELECT count(*) from snippet_tag where tag_id = tag.id
+        and snippet_id in (SELECT id from snippet where trash_id is null)) as snippet_count,
+        created from tag order by name",
+    )?;
+
+    let tag_iter = stmt.query_map(params![], |row| {
+        Ok(item::Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            entity_count: row.get(2)?,
+            snippet_count: row.get(3)?,
+            created: row.get(4)?,
+        })
+    })?;
+
+    let mut tmp_vec = Vec::new();
+    for tag in tag_iter {
+        tmp_vec.push(tag.unwrap());
+    }
+    let cmfs = item::ComfyStruct { data: tmp_vec };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+fn list_attachment(conn: Connection, entity_id: Option<u32>) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT id, entity_id, name, mime_type, size, hash, created from attachment
+        where ((?1) is null or entity_id = (?1)) and trash_id is null order by id",
+    )?;
+
+    let attachment_iter = stmt.query_map(params![entity_id], |row| {
+        Ok(item::Attachment {
+            id: row.get(0)?,
+            entity_id: row.get(1)?,
+            name: row.get(2)?,
+            mime_type: row.get(3)?,
+            size: row.get(4)?,
+            hash: row.get(5)?,
+            created: row.get(6)?,
+        })
+    })?;
+
+    let mut tmp_vec = Vec::new();
+    for attachment in attachment_iter {
+        tmp_vec.push(attachment.unwrap());
+    }
+    let cmfs = item::ComfyStruct { data: tmp_vec };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+fn list_history(conn: Connection, kind: SnippetKind, id: u32) -> rusqlite::Result<()> {
+    let cmfs = item::ComfyStruct {
+        data: revision::get_revisions(&conn, kind, id)?,
+    };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+fn list_diff(
+    conn: Connection,
+    kind: SnippetKind,
+    id: u32,
+    revision_a: u32,
+    revision_b: u32,
+) -> rusqlite::Result<()> {
+    let mut texts = Vec::new();
+    for &revision in &[revision_a, revision_b] {
+        match revision::get_revision(&conn, kind, id, revision)? {
+            Some(text) => texts.push(text),
+            None => {
+                println!("revision {} does not exist. Nothing to compare!", revision);
+                return Ok(());
+            }
+        }
+    }
+
+    let diff = revision::unified_diff(
+        &texts[0],
+        &texts[1],
+        &format!("revision {}", revision_a),
+        &format!("revision {}", revision_b),
+    );
+    if diff.is_empty() {
+        println!("revisions {} and {} are identical", revision_a, revision_b);
+    } else {
+        print!("{}", diff);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,951 @@
+use rusqlite::Connection;
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io, path,
+};
+use time::{Date, Format, OffsetDateTime};
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::export::{
+    self, AliasDocument, Document, DocumentError, EntityDocument, RelationDocument, SnippetDocument,
+};
+use crate::link;
+
+// Lists the files an export wrote into a directory, only those are ever replaced or removed
+const MANIFEST_FILE: &str = ".rtend-export";
+
+// Characters that can't be in file names on some platform or that break `[[links]]`
+const RESERVED_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|', '#', '[', ']'];
+
+// Words YAML would read as something other than a string
+const YAML_KEYWORDS: &[&str] = &["true", "false", "yes", "no", "on", "off", "null", "~"];
+
+// A relation as seen from one of its entities, `label` reads from that entity to `other_id`
+// unless `reversed`, then it reads from `other_id` to the entity
+struct Related<'a> {
+    other_id: u32,
+    label: Option<String>,
+    reversed: bool,
+    relation: &'a RelationDocument,
+}
+
+// Aliases go into the front matter as they are, unless YAML would read them differently
+fn yaml_string(value: &str) -> String {
+    let plain = matches!(value.chars().next(), Some(c) if c.is_alphanumeric())
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || " _-.'".contains(c))
+        && !value.ends_with(' ')
+        && !YAML_KEYWORDS.contains(&value.to_lowercase().as_str())
+        && value.parse::<f64>().is_err();
+
+    if plain {
+        value.to_string()
+    } else {
+        serde_json::to_string(value).unwrap()
+    }
+}
+
+fn file_name(alias: &str) -> String {
+    alias
+        .chars()
+        .map(|c| {
+            if RESERVED_CHARS.contains(&c) || c.is_control() {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string()
+}
+
+// Files are named after the first alias of their entity, the entity id tells apart the ones
+// which would get the same name. Names are compared case-insensitively for the sake of
+// filesystems that do so
+fn file_names(document: &Document) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    let mut taken = HashSet::new();
+    for entity in &document.entities {
+        let mut name = entity
+            .aliases
+            .first()
+            .map(|alias| file_name(&alias.name))
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("entity-{}", entity.id));
+        if taken.contains(&name.to_lowercase()) {
+            name = format!("{} ({})", name, entity.id);
+        }
+        taken.insert(name.to_lowercase());
+        names.insert(entity.id, name);
+    }
+
+    names
+}
+
+// `[[#<entity_id>]]` links point at the file of the entity instead
+fn link_files(data: &str, names: &HashMap<u32, String>) -> String {
+    link::rewrite_links(data, |target| {
+        link::entity_id(target).and_then(|id| names.get(&id).cloned())
+    })
+}
+
+fn quote(data: &str) -> String {
+    data.lines()
+        .map(|line| {
+            if line.is_empty() {
+                "  >".to_string()
+            } else {
+                format!("  > {}", line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_entity(
+    entity: &EntityDocument,
+    related: &[Related],
+    names: &HashMap<u32, String>,
+) -> String {
+    let mut lines = vec!["---".to_string(), format!("id: {}", entity.id)];
+    if entity.aliases.is_empty() {
+        lines.push("aliases: []".to_string());
+    } else {
+        lines.push("aliases:".to_string());
+        for alias in &entity.aliases {
+            lines.push(format!("  - {}", yaml_string(&alias.name)));
+        }
+    }
+    if !entity.tags.is_empty() {
+        lines.push("tags:".to_string());
+        for tag in &entity.tags {
+            lines.push(format!("  - {}", yaml_string(tag)));
+        }
+    }
+    lines.push(format!("created: {}", entity.created));
+    lines.push("---".to_string());
+    lines.push(String::new());
+    match entity.aliases.first() {
+        Some(alias) => lines.push(format!("# {}", alias.name)),
+        None => lines.push(format!("# {}", names[&entity.id])),
+    }
+
+    for snippet in &entity.snippets {
+        lines.push(String::new());
+        match &snippet.due {
+            Some(due) => lines.push(format!("## {} (due {})", snippet.created, due)),
+            None => lines.push(format!("## {}", snippet.created)),
+        }
+        lines.push(String::new());
+        lines.push(link_files(&snippet.data, names));
+    }
+
+    if !related.is_empty() {
+        lines.push(String::new());
+        lines.push("## Relations".to_string());
+        lines.push(String::new());
+        let mut quoted = false;
+        for related in related {
+            // Quoted relation snippets would swallow the next item without a blank line
+            if quoted {
+                lines.push(String::new());
+            }
+            quoted = !related.relation.snippets.is_empty();
+            let other = &names[&related.other_id];
+            match (&related.label, related.reversed) {
+                (Some(label), false) => lines.push(format!("- {} [[{}]]", label, other)),
+                (Some(label), true) => lines.push(format!("- [[{}]] {}", other, label)),
+                (None, _) => lines.push(format!("- [[{}]]", other)),
+            }
+            for snippet in &related.relation.snippets {
+                lines.push(String::new());
+                lines.push(quote(&link_files(&snippet.data, names)));
+            }
+        }
+    }
+
+    let mut markdown = lines.join("\n");
+    markdown.push('\n');
+    markdown
+}
+
+// Relations are listed in both of their entities, from the second one they read with the
+// inverse label if there is one
+fn find_related(document: &Document) -> HashMap<u32, Vec<Related<'_>>> {
+    let mut related: HashMap<u32, Vec<Related>> = HashMap::new();
+    for entity in &document.entities {
+        for relation in &entity.relations {
+            related.entry(entity.id).or_default().push(Related {
+                other_id: relation.entity_id,
+                label: relation.label.clone(),
+                reversed: false,
+                relation,
+            });
+        }
+    }
+    for entity in &document.entities {
+        for relation in &entity.relations {
+            if relation.entity_id == entity.id {
+                continue;
+            }
+            related
+                .entry(relation.entity_id)
+                .or_default()
+                .push(Related {
+                    other_id: entity.id,
+                    label: relation
+                        .inverse_label
+                        .clone()
+                        .or_else(|| relation.label.clone()),
+                    reversed: relation.inverse_label.is_none(),
+                    relation,
+                });
+        }
+    }
+
+    related
+}
+
+// The files an earlier export wrote into `dir`, None if it is empty or doesn't exist yet
+fn read_manifest(dir: &path::Path) -> Result<Option<HashSet<String>>, DocumentError> {
+    match fs::read_to_string(dir.join(MANIFEST_FILE)) {
+        // Anything but the name of a note right inside `dir` is ignored
+        Ok(manifest) => Ok(Some(
+            manifest
+                .lines()
+                .filter(|file| file.ends_with(".md") && !file.contains(&['/', '\\'][..]))
+                .map(str::to_string)
+                .collect(),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+                Err(DocumentError::Invalid(format!(
+                    "{} is not empty and no export was made into it, pick an empty directory",
+                    dir.display()
+                )))
+            } else {
+                Ok(None)
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Writes one file per live entity into `dir` and removes the files of an earlier export
+// whose entity is gone, returns how many were written and how many removed
+pub fn export_vault(conn: &Connection, dir: &path::Path) -> Result<(usize, usize), DocumentError> {
+    let document = export::read_document(conn)?;
+    let names = file_names(&document);
+    let related = find_related(&document);
+
+    let previous = read_manifest(dir)?.unwrap_or_default();
+    let files: Vec<String> = document
+        .entities
+        .iter()
+        .map(|entity| format!("{}.md", names[&entity.id]))
+        .collect();
+    // Notes added by hand next to the exported ones are not overwritten either
+    if let Some(file) = files
+        .iter()
+        .find(|file| !previous.contains(*file) && dir.join(file).exists())
+    {
+        return Err(DocumentError::Invalid(format!(
+            "{} was not written by an export, move it out of the way first",
+            dir.join(file).display()
+        )));
+    }
+
+    fs::create_dir_all(dir)?;
+    for (entity, file) in document.entities.iter().zip(&files) {
+        let markdown = render_entity(
+            entity,
+            related.get(&entity.id).map_or(&[], |related| &related[..]),
+            &names,
+        );
+        fs::write(dir.join(file), markdown)?;
+    }
+    let mut manifest = files.clone();
+    manifest.sort();
+    fs::write(
+        dir.join(MANIFEST_FILE),
+        manifest
+            .iter()
+            .map(|file| format!("{}\n", file))
+            .collect::<String>(),
+    )?;
+
+    let written: HashSet<&String> = files.iter().collect();
+    let mut removed = 0;
+    for file in &previous {
+        if written.contains(file) || !dir.join(file).is_file() {
+            continue;
+        }
+        fs::remove_file(dir.join(file))?;
+        removed += 1;
+    }
+
+    Ok((files.len(), removed))
+}
+
+// A relation found in a directory being imported, `found_in` is the index of the file
+struct NoteRelation {
+    entity_id_a: u32,
+    entity_id_b: u32,
+    label: Option<String>,
+    inverse_label: Option<String>,
+    snippets: Vec<String>,
+    found_in: usize,
+}
+
+// A `- label [[target]]` item of a `Relations` section with the quotes underneath
+struct RelationItem {
+    target: String,
+    label: Option<String>,
+    reversed: bool,
+    snippets: Vec<String>,
+}
+
+enum Block {
+    Snippet {
+        data: String,
+        created: Option<String>,
+        due: Option<String>,
+    },
+    Relations(Vec<RelationItem>),
+}
+
+struct Note {
+    file: String,
+    aliases: Vec<String>,
+    tags: Vec<String>,
+    created: String,
+    updated: String,
+    blocks: Vec<Block>,
+}
+
+// Markdown files in `dir` and the directories below it, hidden ones like `.git` are skipped
+fn find_notes(dir: &path::Path, notes: &mut Vec<path::PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if name.starts_with('.') => continue,
+            _ => (),
+        }
+        if path.is_dir() {
+            find_notes(&path, notes)?;
+        } else if path.extension().and_then(|extension| extension.to_str()) == Some("md") {
+            notes.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn split_front_matter(markdown: &str) -> (Option<&str>, &str) {
+    if let Some(rest) = markdown.strip_prefix("---\n") {
+        let mut offset = 0;
+        for line in rest.split_inclusive('\n') {
+            if line.trim_end() == "---" || line.trim_end() == "..." {
+                return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+            }
+            offset += line.len();
+        }
+    }
+
+    (None, markdown)
+}
+
+fn yaml_strings(yaml: &Yaml) -> Vec<String> {
+    match yaml {
+        Yaml::Array(values) => values.iter().flat_map(yaml_strings).collect(),
+        Yaml::String(value) | Yaml::Real(value) => vec![value.clone()],
+        Yaml::Integer(value) => vec![value.to_string()],
+        Yaml::Boolean(value) => vec![value.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+// RFC 3339 timestamps are kept as they are, a plain date is taken as midnight UTC
+fn read_time(time: &str) -> Option<String> {
+    if OffsetDateTime::parse(time, Format::Rfc3339).is_ok() {
+        Some(time.to_string())
+    } else {
+        Date::parse(time, "%Y-%m-%d")
+            .ok()
+            .map(|date| format!("{}T00:00:00+00:00", date.format("%Y-%m-%d")))
+    }
+}
+
+// The file an export would write for the alias, so it doesn't become an alias of its own
+fn names_file(alias: &str, stem: &str, id: Option<u32>) -> bool {
+    let name = file_name(alias);
+    name.eq_ignore_ascii_case(stem)
+        || matches!(id, Some(id) if format!("{} ({})", name, id).eq_ignore_ascii_case(stem))
+}
+
+// `## <created> (due <date>)` headings of exported snippets
+fn read_snippet_heading(heading: &str) -> Option<(String, Option<String>)> {
+    let (created, due) = match heading.find(" (due ") {
+        Some(start) => (
+            &heading[..start],
+            Some(heading[start + 6..].strip_suffix(')')?.to_string()),
+        ),
+        None => (heading, None),
+    };
+    OffsetDateTime::parse(created, Format::Rfc3339).ok()?;
+    if let Some(due) = &due {
+        Date::parse(due, "%Y-%m-%d").ok()?;
+    }
+
+    Some((created.to_string(), due))
+}
+
+fn heading_text(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    if text.len() < line.len() && line.len() - text.len() <= 6 && text.starts_with(' ') {
+        Some(text.trim())
+    } else {
+        None
+    }
+}
+
+// Lines grouped by the headings they are under, fenced code is never split
+fn split_sections(body: &str) -> Vec<(Option<String>, Vec<String>)> {
+    let mut sections: Vec<(Option<String>, Vec<String>)> = vec![(None, Vec::new())];
+    let mut fenced = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        match heading_text(line) {
+            Some(_) if !fenced => sections.push((Some(line.to_string()), Vec::new())),
+            _ => sections.last_mut().unwrap().1.push(line.to_string()),
+        }
+    }
+
+    sections
+}
+
+fn paragraphs(lines: &[String]) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut fenced = false;
+    for line in lines {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        if line.trim().is_empty() && !fenced {
+            if !paragraph.is_empty() {
+                paragraphs.push(paragraph.join("\n"));
+                paragraph.clear();
+            }
+        } else {
+            paragraph.push(line);
+        }
+    }
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph.join("\n"));
+    }
+
+    paragraphs
+}
+
+fn read_relation_items(lines: &[String]) -> Vec<RelationItem> {
+    let mut items: Vec<RelationItem> = Vec::new();
+    let mut quote: Vec<&str> = Vec::new();
+    for line in lines {
+        let trimmed = line.trim();
+        if let Some(quoted) = trimmed.strip_prefix('>') {
+            if !items.is_empty() {
+                quote.push(quoted.strip_prefix(' ').unwrap_or(quoted));
+            }
+            continue;
+        }
+        if !quote.is_empty() {
+            items.last_mut().unwrap().snippets.push(quote.join("\n"));
+            quote.clear();
+        }
+
+        let item = match trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            Some(item) => item,
+            None => continue,
+        };
+        let (start, end) = match (item.find("[["), item.find("]]")) {
+            (Some(start), Some(end)) if start < end => (start, end),
+            _ => continue,
+        };
+        let before = item[..start].trim();
+        let after = item[end + 2..].trim();
+        let label = [before, after]
+            .iter()
+            .find(|label| !label.is_empty())
+            .map(|label| label.to_string());
+        items.push(RelationItem {
+            target: item[start + 2..end].trim().to_string(),
+            label,
+            reversed: before.is_empty() && !after.is_empty(),
+            snippets: Vec::new(),
+        });
+    }
+    if !quote.is_empty() {
+        items.last_mut().unwrap().snippets.push(quote.join("\n"));
+    }
+
+    items
+}
+
+// Text outside of headings becomes a snippet per paragraph, every other heading a snippet
+// along with the text under it. A `# Title` naming the note itself is left out
+fn read_blocks(body: &str, aliases: &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for (i, (heading, lines)) in split_sections(body).into_iter().enumerate() {
+        let heading = match heading {
+            Some(heading) => heading,
+            None => {
+                for data in paragraphs(&lines) {
+                    blocks.push(Block::Snippet {
+                        data,
+                        created: None,
+                        due: None,
+                    });
+                }
+                continue;
+            }
+        };
+
+        let text = heading_text(&heading).unwrap();
+        let content = paragraphs(&lines).join("\n\n");
+        if i == 1
+            && heading.starts_with("# ")
+            && aliases.iter().any(|alias| alias.eq_ignore_ascii_case(text))
+        {
+            for data in paragraphs(&lines) {
+                blocks.push(Block::Snippet {
+                    data,
+                    created: None,
+                    due: None,
+                });
+            }
+        } else if text.eq_ignore_ascii_case("relations") {
+            blocks.push(Block::Relations(read_relation_items(&lines)));
+        } else if let Some((created, due)) = read_snippet_heading(text) {
+            if !content.is_empty() {
+                blocks.push(Block::Snippet {
+                    data: content,
+                    created: Some(created),
+                    due,
+                });
+            }
+        } else if content.is_empty() {
+            blocks.push(Block::Snippet {
+                data: heading,
+                created: None,
+                due: None,
+            });
+        } else {
+            blocks.push(Block::Snippet {
+                data: format!("{}\n\n{}", heading, content),
+                created: None,
+                due: None,
+            });
+        }
+    }
+
+    blocks
+}
+
+fn read_note(
+    dir: &path::Path,
+    path: &path::Path,
+    problems: &mut Vec<String>,
+) -> Result<Note, DocumentError> {
+    let file = path.strip_prefix(dir).unwrap_or(path).display().to_string();
+    let markdown = fs::read_to_string(path)?.replace("\r\n", "\n");
+    let updated = export::to_rfc3339(OffsetDateTime::from(fs::metadata(path)?.modified()?));
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let (front_matter, body) = split_front_matter(&markdown);
+    let front_matter = match front_matter.map(YamlLoader::load_from_str) {
+        Some(Ok(mut documents)) if !documents.is_empty() => documents.remove(0),
+        Some(Err(e)) => {
+            problems.push(format!(
+                "the front matter of {} could not be read, error: {}",
+                file, e
+            ));
+            Yaml::Null
+        }
+        _ => Yaml::Null,
+    };
+
+    let id = front_matter["id"].as_i64().map(|id| id as u32);
+    let mut aliases: Vec<String> = Vec::new();
+    let front_aliases: Vec<String> = ["aliases", "alias"]
+        .iter()
+        .flat_map(|key| yaml_strings(&front_matter[*key]))
+        .collect();
+    let stem_is_id = matches!(id, Some(id) if stem == format!("entity-{}", id));
+    if !stem_is_id
+        && !front_aliases
+            .iter()
+            .any(|alias| names_file(alias, &stem, id))
+    {
+        aliases.push(stem);
+    }
+    for alias in front_aliases {
+        let alias = alias.trim().to_string();
+        if !alias.is_empty()
+            && !aliases
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(&alias))
+        {
+            aliases.push(alias);
+        }
+    }
+
+    let tags = ["tags", "tag"]
+        .iter()
+        .flat_map(|key| yaml_strings(&front_matter[*key]))
+        .map(|tag| tag.trim_start_matches('#').trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    let created = match front_matter["created"].as_str() {
+        Some(created) => read_time(created).unwrap_or_else(|| {
+            problems.push(format!(
+                "`{}` in the front matter of {} is not a date, the time the file was changed is used instead",
+                created, file
+            ));
+            updated.clone()
+        }),
+        None => updated.clone(),
+    };
+    let blocks = read_blocks(body, &aliases);
+
+    Ok(Note {
+        file,
+        aliases,
+        tags,
+        created,
+        updated,
+        blocks,
+    })
+}
+
+// Links may point at a heading of a file or have a text of their own, `[[file#heading|text]]`
+fn link_target(target: &str) -> String {
+    let target = target.split('|').next().unwrap_or_default();
+    let target = target.split('#').next().unwrap_or_default().trim();
+    let target = target.rsplit('/').next().unwrap_or_default();
+    target.strip_suffix(".md").unwrap_or(target).to_lowercase()
+}
+
+fn add_relation(relations: &mut Vec<NoteRelation>, new: NoteRelation) {
+    // A relation is listed in the files of both its entities
+    if let Some(relation) = relations.iter_mut().find(|relation| {
+        relation.entity_id_a == new.entity_id_a
+            && relation.entity_id_b == new.entity_id_b
+            && relation.label.as_deref().map(str::to_lowercase)
+                == new.label.as_deref().map(str::to_lowercase)
+    }) {
+        for snippet in new.snippets {
+            if !relation.snippets.contains(&snippet) {
+                relation.snippets.push(snippet);
+            }
+        }
+        return;
+    }
+
+    // From the other side it reads the other way around, maybe with the inverse label
+    if let Some(relation) = relations.iter_mut().find(|relation| {
+        relation.entity_id_a == new.entity_id_b
+            && relation.entity_id_b == new.entity_id_a
+            && relation.found_in != new.found_in
+            && relation.inverse_label.is_none()
+            && relation.label.is_some() == new.label.is_some()
+            && relation.snippets == new.snippets
+    }) {
+        if relation.label.as_deref().map(str::to_lowercase)
+            != new.label.as_deref().map(str::to_lowercase)
+        {
+            relation.inverse_label = new.label;
+        }
+        return;
+    }
+
+    relations.push(new);
+}
+
+fn related(relations: &[NoteRelation], id_a: u32, id_b: u32) -> bool {
+    relations.iter().any(|relation| {
+        (relation.entity_id_a == id_a && relation.entity_id_b == id_b)
+            || (relation.entity_id_a == id_b && relation.entity_id_b == id_a)
+    })
+}
+
+// Turns a directory of Markdown files into a document to import, along with the problems
+// found on the way. Every file becomes an entity, `[[links]]` between files relations
+pub fn read_vault(dir: &path::Path) -> Result<(Document, Vec<String>), DocumentError> {
+    if !dir.is_dir() {
+        return Err(DocumentError::Invalid(format!(
+            "{} is not a directory",
+            dir.display()
+        )));
+    }
+    let mut paths = Vec::new();
+    find_notes(dir, &mut paths)?;
+    paths.sort();
+
+    let mut problems = Vec::new();
+    let mut notes = Vec::new();
+    for path in &paths {
+        notes.push(read_note(dir, path, &mut problems)?);
+    }
+
+    // Notes are told apart by their position, links find them by file name or alias
+    let mut ids: HashMap<String, u32> = HashMap::new();
+    for (i, (note, path)) in notes.iter().zip(&paths).enumerate() {
+        let stem = path.file_stem().and_then(|stem| stem.to_str());
+        for name in stem
+            .iter()
+            .copied()
+            .chain(note.aliases.iter().map(String::as_str))
+        {
+            ids.entry(name.to_lowercase()).or_insert(i as u32 + 1);
+        }
+    }
+
+    let mut relations = Vec::new();
+    for (i, note) in notes.iter().enumerate() {
+        let id = i as u32 + 1;
+        for block in &note.blocks {
+            let items = match block {
+                Block::Relations(items) => items,
+                _ => continue,
+            };
+            for item in items {
+                let other_id = match ids.get(&link_target(&item.target)) {
+                    Some(other_id) => *other_id,
+                    None => {
+                        problems.push(format!(
+                            "[[{}]] in {} matches no file, no relation was made",
+                            item.target, note.file
+                        ));
+                        continue;
+                    }
+                };
+                let (entity_id_a, entity_id_b) = if item.reversed {
+                    (other_id, id)
+                } else {
+                    (id, other_id)
+                };
+                add_relation(
+                    &mut relations,
+                    NoteRelation {
+                        entity_id_a,
+                        entity_id_b,
+                        label: item.label.clone(),
+                        inverse_label: None,
+                        snippets: item.snippets.clone(),
+                        found_in: i,
+                    },
+                );
+            }
+        }
+    }
+
+    // Links in the text relate the notes unless they are related already
+    for (i, note) in notes.iter().enumerate() {
+        let id = i as u32 + 1;
+        for block in &note.blocks {
+            let data = match block {
+                Block::Snippet { data, .. } => data,
+                _ => continue,
+            };
+            for target in link::parse_links(data) {
+                if link::entity_id(&target).is_some() {
+                    continue;
+                }
+                match ids.get(&link_target(&target)) {
+                    Some(other_id) if *other_id == id || related(&relations, id, *other_id) => {}
+                    Some(other_id) => relations.push(NoteRelation {
+                        entity_id_a: id,
+                        entity_id_b: *other_id,
+                        label: None,
+                        inverse_label: None,
+                        snippets: Vec::new(),
+                        found_in: i,
+                    }),
+                    None => problems.push(format!(
+                        "[[{}]] in {} matches no file, it stays a link but no relation was made",
+                        target, note.file
+                    )),
+                }
+            }
+        }
+    }
+
+    let mut entities = Vec::new();
+    for (i, note) in notes.into_iter().enumerate() {
+        let id = i as u32 + 1;
+        let Note {
+            aliases,
+            tags,
+            created,
+            updated,
+            blocks,
+            ..
+        } = note;
+        let mut snippets = Vec::new();
+        for block in blocks {
+            // Snippets of an export have the time they were made in their heading
+            if let Block::Snippet {
+                data,
+                created: snippet_created,
+                due,
+            } = block
+            {
+                snippets.push(SnippetDocument {
+                    data,
+                    due,
+                    tags: Vec::new(),
+                    created: snippet_created.clone().unwrap_or_else(|| created.clone()),
+                    updated: snippet_created.unwrap_or_else(|| updated.clone()),
+                });
+            }
+        }
+        let note_relations = relations
+            .iter()
+            .filter(|relation| relation.entity_id_a == id)
+            .map(|relation| RelationDocument {
+                entity_id: relation.entity_id_b,
+                label: relation.label.clone(),
+                inverse_label: relation.inverse_label.clone(),
+                created: created.clone(),
+                updated: updated.clone(),
+                snippets: relation
+                    .snippets
+                    .iter()
+                    .map(|data| SnippetDocument {
+                        data: data.clone(),
+                        due: None,
+                        tags: Vec::new(),
+                        created: created.clone(),
+                        updated: updated.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        entities.push(EntityDocument {
+            id,
+            created: created.clone(),
+            aliases: aliases
+                .iter()
+                .map(|name| AliasDocument {
+                    name: name.clone(),
+                    created: created.clone(),
+                    updated: updated.clone(),
+                })
+                .collect(),
+            tags,
+            properties: Vec::new(),
+            snippets,
+            relations: note_relations,
+        });
+    }
+
+    Ok((
+        Document {
+            version: export::DOCUMENT_VERSION,
+            exported: export::to_rfc3339(OffsetDateTime::now_utc()),
+            entities,
+        },
+        problems,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_relation_items, split_front_matter};
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn front_matter_is_split_off() {
+        assert_eq!(
+            split_front_matter("---\naliases: [Bob]\n---\n# Bob\n"),
+            (Some("aliases: [Bob]\n"), "# Bob\n")
+        );
+        assert_eq!(
+            split_front_matter("---\ntags: []\n...\nbody"),
+            (Some("tags: []\n"), "body")
+        );
+        assert_eq!(split_front_matter("---\n---\n"), (Some(""), ""));
+    }
+
+    #[test]
+    fn unclosed_front_matter_is_body() {
+        assert_eq!(
+            split_front_matter("---\naliases: [Bob]\n"),
+            (None, "---\naliases: [Bob]\n")
+        );
+        assert_eq!(split_front_matter("# Bob\n---\n"), (None, "# Bob\n---\n"));
+    }
+
+    #[test]
+    fn relation_items_keep_label_direction_and_quotes() {
+        let items = read_relation_items(&lines(
+            "> dropped, there is no item yet\n\
+             - friend of [[ Alice ]]\n\
+             > met at school\n\
+             >\n\
+             > still close\n\
+             * [[Carol]] manages\n\
+             - [[Dave]]\n\
+             - not a relation\n\
+             - ]] [[broken\n\
+             > belongs to the last item",
+        ));
+
+        let read: Vec<_> = items
+            .iter()
+            .map(|item| {
+                (
+                    item.target.as_str(),
+                    item.label.as_deref(),
+                    item.reversed,
+                    item.snippets.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            read,
+            vec![
+                (
+                    "Alice",
+                    Some("friend of"),
+                    false,
+                    vec!["met at school\n\nstill close".to_string()]
+                ),
+                ("Carol", Some("manages"), true, vec![]),
+                (
+                    "Dave",
+                    None,
+                    false,
+                    vec!["belongs to the last item".to_string()]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, Connection};
+use std::{process, str::FromStr};
+
+use crate::link;
+use crate::property;
+use crate::revision::SnippetKind;
+use crate::utils;
+
+pub fn merge(args: &ArgMatches, conn: Connection) {
+    let keep_id = u32::from_str(args.value_of("keep_id").unwrap()).unwrap_or_else(|_err| {
+        eprintln!("keep_id must be an u32");
+        process::exit(1);
+    });
+    let absorb_id = u32::from_str(args.value_of("absorb_id").unwrap()).unwrap_or_else(|_err| {
+        eprintln!("absorb_id must be an u32");
+        process::exit(1);
+    });
+
+    if keep_id == absorb_id {
+        eprintln!("Cannot merge an entity into itself");
+        process::exit(1);
+    }
+
+    println!(
+        "This operation will move everything of entity id {} to entity id {}, `rtend undo` reverts it",
+        absorb_id, keep_id
+    );
+    if utils::get_yn_input().unwrap() {
+        match merge_entities(conn, keep_id, absorb_id) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not merge entities, error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        println!("Aborted");
+        process::exit(1);
+    }
+}
+
+fn merge_entities(mut conn: Connection, keep_id: u32, absorb_id: u32) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    let entity_count: u32 = tx.query_row_and_then(
+        "SELECT count(*) from entity where id in (?1, ?2) and trash_id is null",
+        params![keep_id, absorb_id],
+        |row| row.get(0),
+    )?;
+    if entity_count != 2 {
+        println!(
+            "entity id {} or entity id {} does not exist. Nothing got merged!",
+            keep_id, absorb_id
+        );
+        return Ok(());
+    }
+
+    // Aliases the kept entity already has would only show up twice
+    let duplicate_alias_count = tx.execute(
+        "DELETE from alias where entity_id = (?2)
+        and name in (SELECT name from alias where entity_id = (?1) and trash_id is null)",
+        params![keep_id, absorb_id],
+    )?;
+    let alias_count = tx.execute(
+        "UPDATE alias set entity_id = (?1) where entity_id = (?2)",
+        params![keep_id, absorb_id],
+    )?;
+    let snippet_count = tx.execute(
+        "UPDATE snippet set entity_id = (?1) where entity_id = (?2)",
+        params![keep_id, absorb_id],
+    )?;
+    let attachment_count = tx.execute(
+        "UPDATE attachment set entity_id = (?1) where entity_id = (?2)",
+        params![keep_id, absorb_id],
+    )?;
+    // Snippets created from here on have their links parsed at the end
+    let first_new_snippet_id: u32 = tx.query_row_and_then(
+        "SELECT coalesce(max(id), 0) + 1 from snippet",
+        params![],
+        |row| row.get(0),
+    )?;
+    // A key the kept entity already has keeps its value, differing values of the absorbed
+    // entity are kept as snippets so nothing is lost
+    let property_snippet_count = tx.execute(
+        &format!(
+            "INSERT INTO snippet (entity_id, data, created, updated)
+            SELECT (?1), key || ': ' || {}, created, updated from property
+            where entity_id = (?2) and trash_id is null and key in
+            (SELECT key from property keep where keep.entity_id = (?1) and keep.trash_id is null
+            and not (keep.type = property.type and keep.value = property.value))",
+            property::DISPLAY_VALUE
+        ),
+        params![keep_id, absorb_id],
+    )?;
+    let duplicate_property_count = tx.execute(
+        "DELETE from property where entity_id = (?2) and trash_id is null
+        and key in (SELECT key from property where entity_id = (?1) and trash_id is null)",
+        params![keep_id, absorb_id],
+    )?;
+    let property_count = tx.execute(
+        "UPDATE property set entity_id = (?1) where entity_id = (?2)",
+        params![keep_id, absorb_id],
+    )?;
+    tx.execute(
+        "INSERT OR IGNORE INTO entity_tag (entity_id, tag_id, created)
+        SELECT (?1), tag_id, created from entity_tag where entity_id = (?2)",
+        params![keep_id, absorb_id],
+    )?;
+
+    // Relations between the two entities would turn into self-relations, their relation
+    // snippets are kept as snippets of the kept entity instead
+    let self_relation_snippet_count = tx.execute(
+        "INSERT INTO snippet (entity_id, data, created, updated, trash_id)
+        SELECT (?1), data, created, updated, trash_id from relation_snippet where relation_id in
+        (SELECT id from relation where (entity_id_a = (?1) and entity_id_b = (?2))
+        or (entity_id_a = (?2) and entity_id_b = (?1)))",
+        params![keep_id, absorb_id],
+    )?;
+    let self_relation_count = tx.execute(
+        "DELETE from relation where (entity_id_a = (?1) and entity_id_b = (?2))
+        or (entity_id_a = (?2) and entity_id_b = (?1))",
+        params![keep_id, absorb_id],
+    )?;
+    let relation_count = tx.execute(
+        "UPDATE relation set
+        entity_id_a = (CASE entity_id_a WHEN (?2) THEN (?1) ELSE entity_id_a END),
+        entity_id_b = (CASE entity_id_b WHEN (?2) THEN (?1) ELSE entity_id_b END),
+        updated = datetime('now')
+        where entity_id_a = (?2) or entity_id_b = (?2)",
+        params![keep_id, absorb_id],
+    )?;
+
+    link::relink(&tx, SnippetKind::Snippet, first_new_snippet_id)?;
+
+    // Links by id to the absorbed entity would break, they point to the kept one instead. The
+    // snippets keep their `updated`, their text before is kept as a revision
+    let absorbed_target = format!("#{}", absorb_id);
+    let mut relinked_count = 0;
+    for kind in &[SnippetKind::Snippet, SnippetKind::RelationSnippet] {
+        let mut stmt = tx.prepare(&format!(
+            "SELECT id, data from {0} where id in (SELECT {0}_id from link where target = (?))",
+            kind.table()
+        ))?;
+        let snippets = stmt
+            .query_map(params![absorbed_target], |row| {
+                Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+
+        for (id, data) in snippets {
+            let data = link::rewrite_links(&data, |target| {
+                if target == absorbed_target {
+                    Some(format!("#{}", keep_id))
+                } else {
+                    None
+                }
+            });
+            tx.execute(
+                &format!("UPDATE {} set data = (?1) where id = (?2)", kind.table()),
+                params![data, id],
+            )?;
+            link::update_links(&tx, *kind, id, &data)?;
+            relinked_count += 1;
+        }
+    }
+
+    // Only the bare entity and its tags are left at this point
+    tx.execute("DELETE from entity where id = (?)", params![absorb_id])?;
+    tx.commit()?;
+
+    println!(
+        "{} aliases of entity_id {} moved, {} duplicate aliases dropped",
+        alias_count, absorb_id, duplicate_alias_count
+    );
+    println!(
+        "{} snippets of entity_id {} moved",
+        snippet_count, absorb_id
+    );
+    println!(
+        "{} relations of entity_id {} moved",
+        relation_count, absorb_id
+    );
+    if self_relation_count > 0 {
+        println!(
+            "{} relations between entity_id {} and entity_id {} dropped, their {} relation snippets became snippets",
+            self_relation_count, keep_id, absorb_id, self_relation_snippet_count
+        );
+    }
+    println!(
+        "{} attachments of entity_id {} moved",
+        attachment_count, absorb_id
+    );
+    println!(
+        "{} properties of entity_id {} moved, {} already set on entity_id {} dropped",
+        property_count, absorb_id, duplicate_property_count, keep_id
+    );
+    if property_snippet_count > 0 {
+        println!(
+            "{} properties of entity_id {} had different values, they became snippets",
+            property_snippet_count, absorb_id
+        );
+    }
+    if relinked_count > 0 {
+        println!(
+            "{} snippets linking to [[#{}]] now link to [[#{}]], their previous text is kept as a revision",
+            relinked_count, absorb_id, keep_id
+        );
+    }
+    println!(
+        "entity id `{}` merged into entity id `{}`",
+        absorb_id, keep_id
+    );
+
+    Ok(())
+}
@@ -0,0 +1,954 @@
+use rusqlite::{params, Connection, NO_PARAMS};
+use std::fmt;
+
+use crate::link;
+use crate::revision::SnippetKind;
+
+// Version which added the link table, see `migrate`
+const LINK_VERSION: u32 = 12;
+
+// Every migration brings the schema from version `n` to version `n + 1`, where `n` is its index
+// in this list. Released migrations must never be edited, append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: Initial schema
+    "CREATE TABLE entity (
+        id integer primary key autoincrement,
+        created datetime not null default current_timestamp
+    );
+
+    CREATE TABLE snippet (
+        id integer primary key,
+        entity_id integer references entity(id),
+        data text not null,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );
+
+    CREATE TABLE alias (
+        id integer primary key,
+        entity_id integer references entity(id),
+        name varchar(255) not null,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );
+
+    CREATE TABLE relation (
+        id integer primary key,
+        entity_id_a integer not null references entity(id),
+        entity_id_b integer not null references entity(id),
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );
+
+    CREATE TABLE relation_snippet (
+        id integer primary key,
+        relation_id integer not null references relation(id),
+        data text not null,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );",
+    // 2: Foreign keys cascade on delete, orphans left behind by older versions are dropped
+    "DELETE from alias where entity_id is null or entity_id not in (SELECT id from entity);
+    DELETE from snippet where entity_id is null or entity_id not in (SELECT id from entity);
+    DELETE from relation where entity_id_a not in (SELECT id from entity)
+        or entity_id_b not in (SELECT id from entity);
+    DELETE from relation_snippet where relation_id not in (SELECT id from relation);
+
+    CREATE TABLE new_alias (
+        id integer primary key,
+        entity_id integer not null references entity(id) on delete cascade,
+        name varchar(255) not null,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );
+    INSERT INTO new_alias SELECT id, entity_id, name, created, updated from alias;
+    DROP TABLE alias;
+    ALTER TABLE new_alias RENAME TO alias;
+
+    CREATE TABLE new_snippet (
+        id integer primary key,
+        entity_id integer not null references entity(id) on delete cascade,
+        data text not null,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );
+    INSERT INTO new_snippet SELECT id, entity_id, data, created, updated from snippet;
+    DROP TABLE snippet;
+    ALTER TABLE new_snippet RENAME TO snippet;
+
+    CREATE TABLE new_relation (
+        id integer primary key,
+        entity_id_a integer not null references entity(id) on delete cascade,
+        entity_id_b integer not null references entity(id) on delete cascade,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );
+    INSERT INTO new_relation SELECT id, entity_id_a, entity_id_b, created, updated from relation;
+    DROP TABLE relation;
+    ALTER TABLE new_relation RENAME TO relation;
+
+    CREATE TABLE new_relation_snippet (
+        id integer primary key,
+        relation_id integer not null references relation(id) on delete cascade,
+        data text not null,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp
+    );
+    INSERT INTO new_relation_snippet SELECT id, relation_id, data, created, updated from relation_snippet;
+    DROP TABLE relation_snippet;
+    ALTER TABLE new_relation_snippet RENAME TO relation_snippet;
+
+    CREATE INDEX alias_entity_id on alias(entity_id);
+    CREATE INDEX snippet_entity_id on snippet(entity_id);
+    CREATE INDEX relation_entity_id_a on relation(entity_id_a);
+    CREATE INDEX relation_entity_id_b on relation(entity_id_b);
+    CREATE INDEX relation_snippet_relation_id on relation_snippet(relation_id);",
+    // 3: Full-text search indexes, kept in sync with their content tables by triggers
+    "CREATE VIRTUAL TABLE snippet_fts USING fts5(
+        data, content = 'snippet', content_rowid = 'id', prefix = '2 3'
+    );
+    CREATE TRIGGER snippet_fts_insert AFTER INSERT ON snippet BEGIN
+        INSERT INTO snippet_fts (rowid, data) VALUES (new.id, new.data);
+    END;
+    CREATE TRIGGER snippet_fts_delete AFTER DELETE ON snippet BEGIN
+        INSERT INTO snippet_fts (snippet_fts, rowid, data) VALUES ('delete', old.id, old.data);
+    END;
+    CREATE TRIGGER snippet_fts_update AFTER UPDATE OF data ON snippet BEGIN
+        INSERT INTO snippet_fts (snippet_fts, rowid, data) VALUES ('delete', old.id, old.data);
+        INSERT INTO snippet_fts (rowid, data) VALUES (new.id, new.data);
+    END;
+    INSERT INTO snippet_fts (snippet_fts) VALUES ('rebuild');
+
+    CREATE VIRTUAL TABLE relation_snippet_fts USING fts5(
+        data, content = 'relation_snippet', content_rowid = 'id', prefix = '2 3'
+    );
+    CREATE TRIGGER relation_snippet_fts_insert AFTER INSERT ON relation_snippet BEGIN
+        INSERT INTO relation_snippet_fts (rowid, data) VALUES (new.id, new.data);
+    END;
+    CREATE TRIGGER relation_snippet_fts_delete AFTER DELETE ON relation_snippet BEGIN
+        INSERT INTO relation_snippet_fts (relation_snippet_fts, rowid, data)
+        VALUES ('delete', old.id, old.data);
+    END;
+    CREATE TRIGGER relation_snippet_fts_update AFTER UPDATE OF data ON relation_snippet BEGIN
+        INSERT INTO relation_snippet_fts (relation_snippet_fts, rowid, data)
+        VALUES ('delete', old.id, old.data);
+        INSERT INTO relation_snippet_fts (rowid, data) VALUES (new.id, new.data);
+    END;
+    INSERT INTO relation_snippet_fts (relation_snippet_fts) VALUES ('rebuild');",
+    // 4: Tags on entities and snippets
+    "CREATE TABLE tag (
+        id integer primary key,
+        name varchar(255) not null unique collate nocase,
+        created datetime not null default current_timestamp
+    );
+
+    CREATE TABLE entity_tag (
+        entity_id integer not null references entity(id) on delete cascade,
+        tag_id integer not null references tag(id) on delete cascade,
+        created datetime not null default current_timestamp,
+        primary key (entity_id, tag_id)
+    );
+
+    CREATE TABLE snippet_tag (
+        snippet_id integer not null references snippet(id) on delete cascade,
+        tag_id integer not null references tag(id) on delete cascade,
+        created datetime not null default current_timestamp,
+        primary key (snippet_id, tag_id)
+    );
+
+    CREATE INDEX entity_tag_tag_id on entity_tag(tag_id);
+    CREATE INDEX snippet_tag_tag_id on snippet_tag(tag_id);",
+    // 5: Relation labels, `a <label> b` reads as `b <inverse_label> a` from the other side
+    "CREATE TABLE relation_type (
+        id integer primary key,
+        label varchar(255) not null unique collate nocase,
+        inverse_label varchar(255) collate nocase,
+        created datetime not null default current_timestamp
+    );
+
+    ALTER TABLE relation ADD COLUMN relation_type_id integer
+        references relation_type(id) on delete set null;
+
+    CREATE VIEW directed_relation AS
+    SELECT relation.id, relation.relation_type_id,
+        relation.entity_id_a as entity_id, relation.entity_id_b as other_entity_id,
+        coalesce(relation_type.label, '') as label, relation.updated
+    from relation left join relation_type on relation_type.id = relation.relation_type_id
+    UNION ALL
+    SELECT relation.id, relation.relation_type_id,
+        relation.entity_id_b, relation.entity_id_a,
+        coalesce(relation_type.inverse_label, '<- ' || relation_type.label, ''), relation.updated
+    from relation left join relation_type on relation_type.id = relation.relation_type_id
+    where relation.entity_id_a != relation.entity_id_b;",
+    // 6: Revision history, the previous text is kept every time a snippet is edited
+    "CREATE TABLE snippet_revision (
+        id integer primary key,
+        snippet_id integer not null references snippet(id) on delete cascade,
+        revision integer not null,
+        data text not null,
+        created datetime not null default current_timestamp,
+        unique (snippet_id, revision)
+    );
+    CREATE TRIGGER snippet_revision_update AFTER UPDATE OF data ON snippet
+    WHEN old.data != new.data BEGIN
+        INSERT INTO snippet_revision (snippet_id, revision, data, created) VALUES (
+            old.id,
+            (SELECT coalesce(max(revision), 0) + 1 from snippet_revision where snippet_id = old.id),
+            old.data, old.updated
+        );
+    END;
+
+    CREATE TABLE relation_snippet_revision (
+        id integer primary key,
+        relation_snippet_id integer not null references relation_snippet(id) on delete cascade,
+        revision integer not null,
+        data text not null,
+        created datetime not null default current_timestamp,
+        unique (relation_snippet_id, revision)
+    );
+    CREATE TRIGGER relation_snippet_revision_update AFTER UPDATE OF data ON relation_snippet
+    WHEN old.data != new.data BEGIN
+        INSERT INTO relation_snippet_revision (relation_snippet_id, revision, data, created) VALUES (
+            old.id,
+            (SELECT coalesce(max(revision), 0) + 1 from relation_snippet_revision
+            where relation_snippet_id = old.id),
+            old.data, old.updated
+        );
+    END;",
+    // 7: Trash bin, deleted things keep the id of the trash entry they were deleted with
+    // so that everything deleted together can be restored together
+    "CREATE TABLE trash (
+        id integer primary key,
+        kind varchar(32) not null,
+        item_id integer not null,
+        created datetime not null default current_timestamp
+    );
+
+    ALTER TABLE entity ADD COLUMN trash_id integer references trash(id);
+    ALTER TABLE alias ADD COLUMN trash_id integer references trash(id);
+    ALTER TABLE snippet ADD COLUMN trash_id integer references trash(id);
+    ALTER TABLE relation ADD COLUMN trash_id integer references trash(id);
+    ALTER TABLE relation_snippet ADD COLUMN trash_id integer references trash(id);
+
+    DROP VIEW directed_relation;
+    CREATE VIEW directed_relation AS
+    SELECT relation.id, relation.relation_type_id,
+        relation.entity_id_a as entity_id, relation.entity_id_b as other_entity_id,
+        coalesce(relation_type.label, '') as label, relation.updated
+    from relation left join relation_type on relation_type.id = relation.relation_type_id
+    where relation.trash_id is null
+    UNION ALL
+    SELECT relation.id, relation.relation_type_id,
+        relation.entity_id_b, relation.entity_id_a,
+        coalesce(relation_type.inverse_label, '<- ' || relation_type.label, ''), relation.updated
+    from relation left join relation_type on relation_type.id = relation.relation_type_id
+    where relation.entity_id_a != relation.entity_id_b and relation.trash_id is null;
+
+    -- Nothing new can hang off a trashed entity or relation, emptying the trash would take it along
+    CREATE TRIGGER alias_trashed_entity BEFORE INSERT ON alias
+    WHEN (SELECT trash_id from entity where id = new.entity_id) is not null BEGIN
+        SELECT RAISE(ABORT, 'entity is in the trash');
+    END;
+    CREATE TRIGGER snippet_trashed_entity BEFORE INSERT ON snippet
+    WHEN (SELECT trash_id from entity where id = new.entity_id) is not null BEGIN
+        SELECT RAISE(ABORT, 'entity is in the trash');
+    END;
+    CREATE TRIGGER relation_trashed_entity BEFORE INSERT ON relation
+    WHEN (SELECT count(*) from entity where id in (new.entity_id_a, new.entity_id_b)
+        and trash_id is not null) > 0 BEGIN
+        SELECT RAISE(ABORT, 'entity is in the trash');
+    END;
+    CREATE TRIGGER relation_snippet_trashed_relation BEFORE INSERT ON relation_snippet
+    WHEN (SELECT trash_id from relation where id = new.relation_id) is not null BEGIN
+        SELECT RAISE(ABORT, 'relation is in the trash');
+    END;",
+    // 8: Files attached to entities, their contents live in a content-addressed store next to
+    // the profile and are looked up by hash
+    "CREATE TABLE attachment (
+        id integer primary key,
+        entity_id integer not null references entity(id) on delete cascade,
+        name varchar(255) not null,
+        mime_type varchar(255) not null,
+        size integer not null,
+        hash char(64) not null,
+        created datetime not null default current_timestamp,
+        trash_id integer references trash(id)
+    );
+
+    CREATE INDEX attachment_entity_id on attachment(entity_id);
+    CREATE INDEX attachment_hash on attachment(hash);
+
+    CREATE TRIGGER attachment_trashed_entity BEFORE INSERT ON attachment
+    WHEN (SELECT trash_id from entity where id = new.entity_id) is not null BEGIN
+        SELECT RAISE(ABORT, 'entity is in the trash');
+    END;",
+    // 9: Typed key/value properties of entities. `value` has no type affinity so numbers stay
+    // numbers, dates are stored as YYYY-MM-DD and bools as 0 or 1
+    "CREATE TABLE property (
+        id integer primary key,
+        entity_id integer not null references entity(id) on delete cascade,
+        key varchar(255) not null,
+        type varchar(8) not null check (type in ('string', 'number', 'date', 'bool')),
+        value not null,
+        created datetime not null default current_timestamp,
+        updated datetime not null default current_timestamp,
+        trash_id integer references trash(id)
+    );
+
+    CREATE UNIQUE INDEX property_entity_key on property(entity_id, key) where trash_id is null;
+    CREATE INDEX property_key_value on property(key, value);
+
+    CREATE TRIGGER property_trashed_entity BEFORE INSERT ON property
+    WHEN (SELECT trash_id from entity where id = new.entity_id) is not null BEGIN
+        SELECT RAISE(ABORT, 'entity is in the trash');
+    END;",
+    // 10: Spaced repetition state of snippets, a snippet without a row was never reviewed.
+    // `interval` is in days and `due` is a local date
+    "CREATE TABLE review (
+        snippet_id integer primary key references snippet(id) on delete cascade,
+        ease real not null,
+        interval integer not null,
+        repetitions integer not null,
+        due date not null,
+        last_reviewed datetime not null default current_timestamp
+    );
+
+    CREATE INDEX review_due on review(due);",
+    // 11: Optional due dates of snippets and relation snippets, as local YYYY-MM-DD dates
+    "ALTER TABLE snippet ADD COLUMN due date;
+    ALTER TABLE relation_snippet ADD COLUMN due date;
+
+    CREATE INDEX snippet_due on snippet(due) where due is not null;
+    CREATE INDEX relation_snippet_due on relation_snippet(due) where due is not null;",
+    // 12: [[links]] written in snippets and relation snippets. `target` is what is written
+    // between the brackets, either an alias or `#<entity_id>`, and is resolved when read so
+    // links follow renamed aliases
+    "CREATE TABLE link (
+        id integer primary key,
+        snippet_id integer references snippet(id) on delete cascade,
+        relation_snippet_id integer references relation_snippet(id) on delete cascade,
+        target varchar(255) not null,
+        check ((snippet_id is null) != (relation_snippet_id is null))
+    );
+
+    CREATE UNIQUE INDEX link_snippet on link(snippet_id, target) where snippet_id is not null;
+    CREATE UNIQUE INDEX link_relation_snippet on link(relation_snippet_id, target)
+        where relation_snippet_id is not null;
+    CREATE INDEX link_target on link(target collate nocase);
+
+    CREATE VIEW resolved_link AS
+    SELECT link.id as link_id, entity.id as entity_id
+    from link join entity on link.target = '#' || entity.id
+    where entity.trash_id is null
+    UNION
+    SELECT link.id, alias.entity_id
+    from link join alias on alias.name = link.target collate nocase
+    where alias.trash_id is null;",
+    // 13: Append-only journal of every change to the user's data. Each run of a command which
+    // changes something gets a `journal_command`, the triggers file the changed rows under the
+    // latest one with the row as JSON before and after the change
+    "CREATE TABLE journal_command (
+        id integer primary key,
+        command text not null,
+        created datetime not null default current_timestamp
+    );
+
+    CREATE TABLE journal (
+        id integer primary key,
+        command_id integer references journal_command(id),
+        operation varchar(8) not null check (operation in ('insert', 'update', 'delete')),
+        kind varchar(32) not null,
+        row_id integer not null,
+        entity_id integer,
+        other_entity_id integer,
+        old_value text,
+        new_value text,
+        created datetime not null default current_timestamp
+    );
+
+    CREATE INDEX journal_command_id on journal(command_id);
+    CREATE INDEX journal_entity_id on journal(entity_id);
+    CREATE INDEX journal_other_entity_id on journal(other_entity_id);
+    CREATE INDEX journal_created on journal(created);
+
+    CREATE TRIGGER journal_no_update BEFORE UPDATE ON journal BEGIN
+        SELECT RAISE(ABORT, 'the journal is append-only');
+    END;
+
+    CREATE TRIGGER journal_no_delete BEFORE DELETE ON journal BEGIN
+        SELECT RAISE(ABORT, 'the journal is append-only');
+    END;
+
+    CREATE TRIGGER journal_entity_insert AFTER INSERT ON entity BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'entity', new.id,
+            new.id, null,
+            null,
+            json_object('id', new.id, 'created', new.created, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_entity_update AFTER UPDATE ON entity BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'entity', new.id,
+            new.id, null,
+            json_object('id', old.id, 'created', old.created, 'trash_id', old.trash_id),
+            json_object('id', new.id, 'created', new.created, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_entity_delete AFTER DELETE ON entity BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'entity', old.id,
+            old.id, null,
+            json_object('id', old.id, 'created', old.created, 'trash_id', old.trash_id),
+            null);
+    END;
+
+    CREATE TRIGGER journal_alias_insert AFTER INSERT ON alias BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'alias', new.id,
+            new.entity_id, null,
+            null,
+            json_object('id', new.id, 'entity_id', new.entity_id, 'name', new.name, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_alias_update AFTER UPDATE ON alias BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'alias', new.id,
+            new.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'name', old.name, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id),
+            json_object('id', new.id, 'entity_id', new.entity_id, 'name', new.name, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_alias_delete AFTER DELETE ON alias BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'alias', old.id,
+            old.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'name', old.name, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id),
+            null);
+    END;
+
+    CREATE TRIGGER journal_snippet_insert AFTER INSERT ON snippet BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'snippet', new.id,
+            new.entity_id, null,
+            null,
+            json_object('id', new.id, 'entity_id', new.entity_id, 'data', new.data, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id, 'due', new.due));
+    END;
+
+    CREATE TRIGGER journal_snippet_update AFTER UPDATE ON snippet BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'snippet', new.id,
+            new.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'data', old.data, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id, 'due', old.due),
+            json_object('id', new.id, 'entity_id', new.entity_id, 'data', new.data, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id, 'due', new.due));
+    END;
+
+    CREATE TRIGGER journal_snippet_delete AFTER DELETE ON snippet BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'snippet', old.id,
+            old.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'data', old.data, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id, 'due', old.due),
+            null);
+    END;
+
+    CREATE TRIGGER journal_relation_insert AFTER INSERT ON relation BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'relation', new.id,
+            new.entity_id_a, new.entity_id_b,
+            null,
+            json_object('id', new.id, 'entity_id_a', new.entity_id_a, 'entity_id_b', new.entity_id_b, 'relation_type_id', new.relation_type_id, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_relation_update AFTER UPDATE ON relation BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'relation', new.id,
+            new.entity_id_a, new.entity_id_b,
+            json_object('id', old.id, 'entity_id_a', old.entity_id_a, 'entity_id_b', old.entity_id_b, 'relation_type_id', old.relation_type_id, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id),
+            json_object('id', new.id, 'entity_id_a', new.entity_id_a, 'entity_id_b', new.entity_id_b, 'relation_type_id', new.relation_type_id, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_relation_delete AFTER DELETE ON relation BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'relation', old.id,
+            old.entity_id_a, old.entity_id_b,
+            json_object('id', old.id, 'entity_id_a', old.entity_id_a, 'entity_id_b', old.entity_id_b, 'relation_type_id', old.relation_type_id, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id),
+            null);
+    END;
+
+    CREATE TRIGGER journal_relation_snippet_insert AFTER INSERT ON relation_snippet BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'relation_snippet', new.id,
+            (SELECT entity_id_a from relation where id = new.relation_id), (SELECT entity_id_b from relation where id = new.relation_id),
+            null,
+            json_object('id', new.id, 'relation_id', new.relation_id, 'data', new.data, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id, 'due', new.due));
+    END;
+
+    CREATE TRIGGER journal_relation_snippet_update AFTER UPDATE ON relation_snippet BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'relation_snippet', new.id,
+            (SELECT entity_id_a from relation where id = new.relation_id), (SELECT entity_id_b from relation where id = new.relation_id),
+            json_object('id', old.id, 'relation_id', old.relation_id, 'data', old.data, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id, 'due', old.due),
+            json_object('id', new.id, 'relation_id', new.relation_id, 'data', new.data, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id, 'due', new.due));
+    END;
+
+    CREATE TRIGGER journal_relation_snippet_delete AFTER DELETE ON relation_snippet BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'relation_snippet', old.id,
+            (SELECT entity_id_a from relation where id = old.relation_id), (SELECT entity_id_b from relation where id = old.relation_id),
+            json_object('id', old.id, 'relation_id', old.relation_id, 'data', old.data, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id, 'due', old.due),
+            null);
+    END;
+
+    CREATE TRIGGER journal_attachment_insert AFTER INSERT ON attachment BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'attachment', new.id,
+            new.entity_id, null,
+            null,
+            json_object('id', new.id, 'entity_id', new.entity_id, 'name', new.name, 'mime_type', new.mime_type, 'size', new.size, 'hash', new.hash, 'created', new.created, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_attachment_update AFTER UPDATE ON attachment BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'attachment', new.id,
+            new.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'name', old.name, 'mime_type', old.mime_type, 'size', old.size, 'hash', old.hash, 'created', old.created, 'trash_id', old.trash_id),
+            json_object('id', new.id, 'entity_id', new.entity_id, 'name', new.name, 'mime_type', new.mime_type, 'size', new.size, 'hash', new.hash, 'created', new.created, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_attachment_delete AFTER DELETE ON attachment BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'attachment', old.id,
+            old.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'name', old.name, 'mime_type', old.mime_type, 'size', old.size, 'hash', old.hash, 'created', old.created, 'trash_id', old.trash_id),
+            null);
+    END;
+
+    CREATE TRIGGER journal_property_insert AFTER INSERT ON property BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'property', new.id,
+            new.entity_id, null,
+            null,
+            json_object('id', new.id, 'entity_id', new.entity_id, 'key', new.key, 'type', new.type, 'value', new.value, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_property_update AFTER UPDATE ON property BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'property', new.id,
+            new.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'key', old.key, 'type', old.type, 'value', old.value, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id),
+            json_object('id', new.id, 'entity_id', new.entity_id, 'key', new.key, 'type', new.type, 'value', new.value, 'created', new.created, 'updated', new.updated, 'trash_id', new.trash_id));
+    END;
+
+    CREATE TRIGGER journal_property_delete AFTER DELETE ON property BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'property', old.id,
+            old.entity_id, null,
+            json_object('id', old.id, 'entity_id', old.entity_id, 'key', old.key, 'type', old.type, 'value', old.value, 'created', old.created, 'updated', old.updated, 'trash_id', old.trash_id),
+            null);
+    END;
+
+    CREATE TRIGGER journal_tag_insert AFTER INSERT ON tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'tag', new.id,
+            null, null,
+            null,
+            json_object('id', new.id, 'name', new.name, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_tag_update AFTER UPDATE ON tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'tag', new.id,
+            null, null,
+            json_object('id', old.id, 'name', old.name, 'created', old.created),
+            json_object('id', new.id, 'name', new.name, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_tag_delete AFTER DELETE ON tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'tag', old.id,
+            null, null,
+            json_object('id', old.id, 'name', old.name, 'created', old.created),
+            null);
+    END;
+
+    CREATE TRIGGER journal_entity_tag_insert AFTER INSERT ON entity_tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'entity_tag', new.rowid,
+            new.entity_id, null,
+            null,
+            json_object('entity_id', new.entity_id, 'tag_id', new.tag_id, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_entity_tag_update AFTER UPDATE ON entity_tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'entity_tag', new.rowid,
+            new.entity_id, null,
+            json_object('entity_id', old.entity_id, 'tag_id', old.tag_id, 'created', old.created),
+            json_object('entity_id', new.entity_id, 'tag_id', new.tag_id, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_entity_tag_delete AFTER DELETE ON entity_tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'entity_tag', old.rowid,
+            old.entity_id, null,
+            json_object('entity_id', old.entity_id, 'tag_id', old.tag_id, 'created', old.created),
+            null);
+    END;
+
+    CREATE TRIGGER journal_snippet_tag_insert AFTER INSERT ON snippet_tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'snippet_tag', new.rowid,
+            (SELECT entity_id from snippet where id = new.snippet_id), null,
+            null,
+            json_object('snippet_id', new.snippet_id, 'tag_id', new.tag_id, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_snippet_tag_update AFTER UPDATE ON snippet_tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'snippet_tag', new.rowid,
+            (SELECT entity_id from snippet where id = new.snippet_id), null,
+            json_object('snippet_id', old.snippet_id, 'tag_id', old.tag_id, 'created', old.created),
+            json_object('snippet_id', new.snippet_id, 'tag_id', new.tag_id, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_snippet_tag_delete AFTER DELETE ON snippet_tag BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'snippet_tag', old.rowid,
+            (SELECT entity_id from snippet where id = old.snippet_id), null,
+            json_object('snippet_id', old.snippet_id, 'tag_id', old.tag_id, 'created', old.created),
+            null);
+    END;
+
+    CREATE TRIGGER journal_relation_type_insert AFTER INSERT ON relation_type BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'relation_type', new.id,
+            null, null,
+            null,
+            json_object('id', new.id, 'label', new.label, 'inverse_label', new.inverse_label, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_relation_type_update AFTER UPDATE ON relation_type BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'relation_type', new.id,
+            null, null,
+            json_object('id', old.id, 'label', old.label, 'inverse_label', old.inverse_label, 'created', old.created),
+            json_object('id', new.id, 'label', new.label, 'inverse_label', new.inverse_label, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_relation_type_delete AFTER DELETE ON relation_type BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'relation_type', old.id,
+            null, null,
+            json_object('id', old.id, 'label', old.label, 'inverse_label', old.inverse_label, 'created', old.created),
+            null);
+    END;
+
+    CREATE TRIGGER journal_trash_insert AFTER INSERT ON trash BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'trash', new.id,
+            null, null,
+            null,
+            json_object('id', new.id, 'kind', new.kind, 'item_id', new.item_id, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_trash_update AFTER UPDATE ON trash BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'trash', new.id,
+            null, null,
+            json_object('id', old.id, 'kind', old.kind, 'item_id', old.item_id, 'created', old.created),
+            json_object('id', new.id, 'kind', new.kind, 'item_id', new.item_id, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_trash_delete AFTER DELETE ON trash BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'trash', old.id,
+            null, null,
+            json_object('id', old.id, 'kind', old.kind, 'item_id', old.item_id, 'created', old.created),
+            null);
+    END;",
+    // 14: Undo and redo. `undo` and `redo` commands revert the changes of the command in
+    // `reverts`, which is then marked as `undone`
+    "ALTER TABLE journal_command ADD COLUMN kind varchar(8) not null default 'do'
+        check (kind in ('do', 'undo', 'redo'));
+    ALTER TABLE journal_command ADD COLUMN reverts integer references journal_command(id);
+    ALTER TABLE journal_command ADD COLUMN undone boolean not null default 0;",
+    // 15: Revisions and the review schedule are journaled as well, a forced delete cascades to
+    // them. Undo and redo put revisions back from the journal instead of making new ones
+    "DROP TRIGGER snippet_revision_update;
+    CREATE TRIGGER snippet_revision_update AFTER UPDATE OF data ON snippet
+    WHEN old.data != new.data
+    and coalesce((SELECT kind from journal_command order by id desc limit 1), 'do') = 'do' BEGIN
+        INSERT INTO snippet_revision (snippet_id, revision, data, created) VALUES (
+            old.id,
+            (SELECT coalesce(max(revision), 0) + 1 from snippet_revision where snippet_id = old.id),
+            old.data, old.updated
+        );
+    END;
+
+    DROP TRIGGER relation_snippet_revision_update;
+    CREATE TRIGGER relation_snippet_revision_update AFTER UPDATE OF data ON relation_snippet
+    WHEN old.data != new.data
+    and coalesce((SELECT kind from journal_command order by id desc limit 1), 'do') = 'do' BEGIN
+        INSERT INTO relation_snippet_revision (relation_snippet_id, revision, data, created) VALUES (
+            old.id,
+            (SELECT coalesce(max(revision), 0) + 1 from relation_snippet_revision
+            where relation_snippet_id = old.id),
+            old.data, old.updated
+        );
+    END;
+
+    CREATE TRIGGER journal_snippet_revision_insert AFTER INSERT ON snippet_revision BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'snippet_revision', new.id,
+            (SELECT entity_id from snippet where id = new.snippet_id), null,
+            null,
+            json_object('id', new.id, 'snippet_id', new.snippet_id, 'revision', new.revision, 'data', new.data, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_snippet_revision_update AFTER UPDATE ON snippet_revision BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'snippet_revision', new.id,
+            (SELECT entity_id from snippet where id = new.snippet_id), null,
+            json_object('id', old.id, 'snippet_id', old.snippet_id, 'revision', old.revision, 'data', old.data, 'created', old.created),
+            json_object('id', new.id, 'snippet_id', new.snippet_id, 'revision', new.revision, 'data', new.data, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_snippet_revision_delete AFTER DELETE ON snippet_revision BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'snippet_revision', old.id,
+            (SELECT entity_id from snippet where id = old.snippet_id), null,
+            json_object('id', old.id, 'snippet_id', old.snippet_id, 'revision', old.revision, 'data', old.data, 'created', old.created),
+            null);
+    END;
+
+    CREATE TRIGGER journal_relation_snippet_revision_insert AFTER INSERT ON relation_snippet_revision BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'relation_snippet_revision', new.id,
+            (SELECT entity_id_a from relation where id = (SELECT relation_id from relation_snippet where id = new.relation_snippet_id)), (SELECT entity_id_b from relation where id = (SELECT relation_id from relation_snippet where id = new.relation_snippet_id)),
+            null,
+            json_object('id', new.id, 'relation_snippet_id', new.relation_snippet_id, 'revision', new.revision, 'data', new.data, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_relation_snippet_revision_update AFTER UPDATE ON relation_snippet_revision BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'relation_snippet_revision', new.id,
+            (SELECT entity_id_a from relation where id = (SELECT relation_id from relation_snippet where id = new.relation_snippet_id)), (SELECT entity_id_b from relation where id = (SELECT relation_id from relation_snippet where id = new.relation_snippet_id)),
+            json_object('id', old.id, 'relation_snippet_id', old.relation_snippet_id, 'revision', old.revision, 'data', old.data, 'created', old.created),
+            json_object('id', new.id, 'relation_snippet_id', new.relation_snippet_id, 'revision', new.revision, 'data', new.data, 'created', new.created));
+    END;
+
+    CREATE TRIGGER journal_relation_snippet_revision_delete AFTER DELETE ON relation_snippet_revision BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'relation_snippet_revision', old.id,
+            (SELECT entity_id_a from relation where id = (SELECT relation_id from relation_snippet where id = old.relation_snippet_id)), (SELECT entity_id_b from relation where id = (SELECT relation_id from relation_snippet where id = old.relation_snippet_id)),
+            json_object('id', old.id, 'relation_snippet_id', old.relation_snippet_id, 'revision', old.revision, 'data', old.data, 'created', old.created),
+            null);
+    END;
+
+    CREATE TRIGGER journal_review_insert AFTER INSERT ON review BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'insert', 'review', new.snippet_id,
+            (SELECT entity_id from snippet where id = new.snippet_id), null,
+            null,
+            json_object('snippet_id', new.snippet_id, 'ease', new.ease, 'interval', new.interval, 'repetitions', new.repetitions, 'due', new.due, 'last_reviewed', new.last_reviewed));
+    END;
+
+    CREATE TRIGGER journal_review_update AFTER UPDATE ON review BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'update', 'review', new.snippet_id,
+            (SELECT entity_id from snippet where id = new.snippet_id), null,
+            json_object('snippet_id', old.snippet_id, 'ease', old.ease, 'interval', old.interval, 'repetitions', old.repetitions, 'due', old.due, 'last_reviewed', old.last_reviewed),
+            json_object('snippet_id', new.snippet_id, 'ease', new.ease, 'interval', new.interval, 'repetitions', new.repetitions, 'due', new.due, 'last_reviewed', new.last_reviewed));
+    END;
+
+    CREATE TRIGGER journal_review_delete AFTER DELETE ON review BEGIN
+        INSERT INTO journal (command_id, operation, kind, row_id, entity_id, other_entity_id,
+            old_value, new_value)
+        VALUES ((SELECT max(id) from journal_command), 'delete', 'review', old.snippet_id,
+            (SELECT entity_id from snippet where id = old.snippet_id), null,
+            json_object('snippet_id', old.snippet_id, 'ease', old.ease, 'interval', old.interval, 'repetitions', old.repetitions, 'due', old.due, 'last_reviewed', old.last_reviewed),
+            null);
+    END;",
+    // 16: Who ran each journaled command, older commands have no user
+    "ALTER TABLE journal_command ADD COLUMN user varchar(255);",
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    DatabaseTooNew(u32),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::DatabaseTooNew(version) => write!(
+                f,
+                "database schema version {} is newer than the latest supported version {}, please upgrade rtend",
+                version,
+                latest_version()
+            ),
+            MigrationError::Sqlite(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+pub fn get_user_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.query_row_and_then("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+}
+
+fn table_exists(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    let count: u32 = conn.query_row_and_then(
+        "SELECT count(*) from sqlite_master where type = 'table' and name = (?)",
+        params![name],
+        |row| row.get(0),
+    )?;
+
+    Ok(count > 0)
+}
+
+// Foreign keys are switched off while migrating since rebuilding a table would otherwise
+// trigger the cascades of the tables referencing it, `utils::open_db` turns them back on
+pub fn migrate(conn: &mut Connection) -> Result<(), MigrationError> {
+    conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+
+    let mut version = get_user_version(conn)?;
+    if version > latest_version() {
+        return Err(MigrationError::DatabaseTooNew(version));
+    }
+
+    // Databases created before migrations existed were never stamped,
+    // but they already have the initial schema
+    if version == 0 && table_exists(conn, "entity")? {
+        conn.execute_batch("PRAGMA user_version = 1")?;
+        version = 1;
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", index + 1))?;
+        tx.commit()?;
+    }
+
+    // Links can't be parsed in SQL, the ones in snippets written before the link table
+    // existed are stored once it does
+    if version < LINK_VERSION {
+        let tx = conn.transaction()?;
+        link::relink(&tx, SnippetKind::Snippet, 0)?;
+        link::relink(&tx, SnippetKind::RelationSnippet, 0)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_user_version, latest_version, migrate, MigrationError, MIGRATIONS};
+    use rusqlite::{Connection, NO_PARAMS};
+
+    #[test]
+    fn new_databases_get_every_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), latest_version());
+
+        // Nothing is left to run the second time
+        migrate(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn unstamped_databases_keep_their_data() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        // Versions without foreign keys could leave orphans behind
+        conn.execute_batch(
+            "PRAGMA foreign_keys = OFF;
+            INSERT INTO entity (id) VALUES (1), (2);
+            INSERT INTO alias (entity_id, name) VALUES (1, 'Bob'), (2, 'Alice');
+            INSERT INTO snippet (entity_id, data) VALUES (1, 'knows [[Alice]] and [[#2]]');
+            INSERT INTO snippet (entity_id, data) VALUES (3, 'belongs to nobody');",
+        )
+        .unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), 0);
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(get_user_version(&conn).unwrap(), latest_version());
+
+        let count = |sql: &str| -> u32 {
+            conn.query_row_and_then(sql, NO_PARAMS, |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count("SELECT count(*) from alias"), 2);
+        assert_eq!(count("SELECT count(*) from snippet"), 1);
+        assert_eq!(count("SELECT count(*) from link where snippet_id = 1"), 2);
+        assert_eq!(count("SELECT count(*) from pragma_foreign_key_check"), 0);
+    }
+
+    #[test]
+    fn newer_databases_are_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!("PRAGMA user_version = {}", latest_version() + 1))
+            .unwrap();
+        assert!(matches!(
+            migrate(&mut conn),
+            Err(MigrationError::DatabaseTooNew(version)) if version == latest_version() + 1
+        ));
+    }
+}
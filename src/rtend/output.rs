@@ -0,0 +1,214 @@
+use serde::{
+    de::{Deserialize, Deserializer, MapAccess, Visitor},
+    Serialize,
+};
+use serde_json::Value;
+use std::{fmt, process, str::FromStr};
+
+// Names accepted by `format` and --format
+pub const OUTPUT_FORMATS: &[&str] = &["table", "markdown", "json", "jsonl", "csv", "tsv"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    // A table drawn with the ascii_markdown preset
+    Markdown,
+    Json,
+    Jsonl,
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    pub fn draws_tables(self) -> bool {
+        matches!(self, OutputFormat::Table | OutputFormat::Markdown)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(()),
+        }
+    }
+}
+
+// The names of the fields of a row in the order they are declared in, CSV and TSV name their
+// columns with them even when there are no rows
+pub trait Fields {
+    const FIELDS: &'static [&'static str];
+}
+
+// The fields of a row in the order they are declared in, serde_json's own maps sort them
+struct Record(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for Record {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RecordVisitor;
+
+        impl<'de> Visitor<'de> for RecordVisitor {
+            type Value = Record;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a row")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Record, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(Record(fields))
+            }
+        }
+
+        deserializer.deserialize_map(RecordVisitor)
+    }
+}
+
+fn to_record<T: Serialize>(row: &T) -> serde_json::Result<Record> {
+    serde_json::from_str(&serde_json::to_string(row)?)
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Fields with a separator, a quote or a line break in them are quoted as RFC 4180 says
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// TSV has no quoting, tabs and line breaks are escaped instead
+fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+// The header is there even without rows, so nothing found can be told apart from a failure
+fn print_delimited<T: Serialize + Fields>(
+    rows: &[T],
+    separator: &str,
+    field: fn(&str) -> String,
+) -> serde_json::Result<()> {
+    let header: Vec<String> = T::FIELDS.iter().map(|name| field(name)).collect();
+    println!("{}", header.join(separator));
+
+    for row in rows {
+        let Record(fields) = to_record(row)?;
+        let values: Vec<String> = fields
+            .iter()
+            .map(|(_, value)| field(&to_text(value)))
+            .collect();
+        println!("{}", values.join(separator));
+    }
+
+    Ok(())
+}
+
+fn try_print_records<T: Serialize + Fields>(
+    rows: &[T],
+    format: OutputFormat,
+) -> serde_json::Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+        OutputFormat::Jsonl => {
+            for row in rows {
+                println!("{}", serde_json::to_string(row)?);
+            }
+        }
+        OutputFormat::Csv => print_delimited(rows, ",", csv_field)?,
+        OutputFormat::Tsv => print_delimited(rows, "\t", tsv_field)?,
+        OutputFormat::Table | OutputFormat::Markdown => unreachable!(),
+    }
+
+    Ok(())
+}
+
+// No rows are an empty array as JSON, a header alone as CSV and TSV and nothing as JSON Lines
+pub fn print_records<T: Serialize + Fields>(rows: &[T], format: OutputFormat) {
+    if let Err(e) = try_print_records(rows, format) {
+        eprintln!("Could not write the output, error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, to_record, tsv_field, Fields, Record};
+    use crate::item;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn tsv_fields_are_escaped() {
+        assert_eq!(
+            tsv_field("plain text, \"quoted\""),
+            "plain text, \"quoted\""
+        );
+        assert_eq!(tsv_field("a\tb"), "a\\tb");
+        assert_eq!(tsv_field("two\r\nlines"), "two\\r\\nlines");
+        assert_eq!(tsv_field("back\\slash\\t"), "back\\\\slash\\\\t");
+    }
+
+    #[test]
+    fn fields_are_named_in_order() {
+        let alias = item::Alias {
+            id: 1,
+            name: "Bob".to_string(),
+            updated: OffsetDateTime::unix_epoch(),
+        };
+        let Record(fields) = to_record(&alias).unwrap();
+        let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, item::Alias::FIELDS);
+
+        let entry = item::JournalEntry {
+            id: 1,
+            command: "add --entity Bob".to_string(),
+            user: "alice".to_string(),
+            operation: "insert".to_string(),
+            kind: "entity".to_string(),
+            row_id: 1,
+            old_value: String::new(),
+            new_value: String::new(),
+            created: OffsetDateTime::unix_epoch(),
+        };
+        let Record(fields) = to_record(&entry).unwrap();
+        let names: Vec<&str> = fields.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, item::JournalEntry::FIELDS);
+    }
+}
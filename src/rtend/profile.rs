@@ -0,0 +1,284 @@
+use clap::ArgMatches;
+use rusqlite::{self, Connection, DatabaseName, OpenFlags, NO_PARAMS};
+use std::{ffi::OsStr, fs, io, path, process};
+use time::OffsetDateTime;
+
+use crate::attachment;
+use crate::backup;
+use crate::encryption;
+use crate::item;
+use crate::item::ComfyTable;
+use crate::utils::{self, StoreError};
+
+pub fn profile(args: &ArgMatches) {
+    match args.subcommand() {
+        ("list", Some(_list_matches)) => match list_profiles() {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not list profiles, error: {}", e);
+                process::exit(1);
+            }
+        },
+
+        ("create", Some(create_matches)) => {
+            let name = get_name(create_matches, "name");
+            if profile_exists(name) {
+                println!("profile `{}` already exists. Nothing got created!", name);
+                return;
+            }
+
+            match utils::create_new_db(&db_path(name), None) {
+                Ok(()) => println!("profile `{}` created", name),
+                Err(e) => {
+                    eprintln!("Could not create profile, error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        ("rename", Some(rename_matches)) => {
+            let old_name = get_name(rename_matches, "old_name");
+            let new_name = get_name(rename_matches, "new_name");
+
+            match rename_profile(old_name, new_name) {
+                Ok(()) => (),
+                Err(e) => {
+                    eprintln!("Could not rename profile, error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        ("copy", Some(copy_matches)) => {
+            let name = get_name(copy_matches, "name");
+            let new_name = get_name(copy_matches, "new_name");
+
+            match copy_profile(name, new_name) {
+                Ok(()) => (),
+                Err(e) => {
+                    eprintln!("Could not copy profile, error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        ("delete", Some(delete_matches)) => {
+            let name = get_name(delete_matches, "name");
+            if !profile_exists(name) {
+                println!("profile `{}` does not exist. Nothing got deleted!", name);
+                return;
+            }
+            if name == utils::get_default_profile() {
+                println!(
+                    "profile `{}` is the default profile, set another one as default first. Nothing got deleted!",
+                    name
+                );
+                return;
+            }
+
+            println!(
+                "This operation will delete the profile `{}` along with its attachments and cannot be undone",
+                name
+            );
+            if !utils::get_yn_input().unwrap() {
+                println!("Aborted");
+                process::exit(1);
+            }
+
+            match delete_profile(name) {
+                Ok(()) => println!("profile `{}` deleted", name),
+                Err(e) => {
+                    eprintln!("Could not delete profile, error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        ("set-default", Some(default_matches)) => {
+            let name = get_name(default_matches, "name");
+            if !profile_exists(name) {
+                println!("profile `{}` does not exist, please create it first", name);
+                return;
+            }
+
+            match utils::set_default_profile(name) {
+                Ok(()) => println!("profile `{}` is now the default profile", name),
+                Err(e) => {
+                    eprintln!("Could not set default profile, error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        _ => unreachable!(),
+    }
+}
+
+fn get_name<'a>(args: &'a ArgMatches, arg: &str) -> &'a str {
+    let name = args.value_of(arg).unwrap();
+    if !utils::is_valid_profile_name(name) {
+        eprintln!("profile name must not be empty, start with `.` or contain path separators");
+        process::exit(1);
+    }
+
+    name
+}
+
+fn db_file(name: &str) -> String {
+    format!("{}.db", name)
+}
+
+fn data_dir() -> path::PathBuf {
+    utils::find_data_dir().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    })
+}
+
+fn db_path(name: &str) -> path::PathBuf {
+    data_dir().join(db_file(name))
+}
+
+fn backup_dir(name: &str) -> path::PathBuf {
+    backup::backup_dir(&db_path(name)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    })
+}
+
+fn attachment_dir(name: &str) -> path::PathBuf {
+    attachment::store_dir(&db_path(name))
+}
+
+fn profile_exists(name: &str) -> bool {
+    db_path(name).is_file()
+}
+
+fn count_entities(db_path: &path::Path) -> Option<u32> {
+    if encryption::is_encrypted(db_path) {
+        return None;
+    }
+
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY).ok()?;
+    conn.query_row_and_then(
+        "SELECT count(*) from entity where trash_id is null",
+        NO_PARAMS,
+        |row| row.get(0),
+    )
+    .or_else(|_| {
+        // Profiles which were not opened since the trash was added
+        conn.query_row_and_then("SELECT count(*) from entity", NO_PARAMS, |row| row.get(0))
+    })
+    .ok()
+}
+
+fn list_profiles() -> io::Result<()> {
+    let data_dir = data_dir();
+    let default_profile = utils::get_default_profile();
+
+    let mut tmp_vec = Vec::new();
+    if data_dir.exists() {
+        for entry in fs::read_dir(&data_dir)? {
+            let entry_path = entry?.path();
+            if !entry_path.is_file() || entry_path.extension() != Some(OsStr::new("db")) {
+                continue;
+            }
+
+            let name = entry_path
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            let metadata = fs::metadata(&entry_path)?;
+            tmp_vec.push(item::Profile {
+                default: name == default_profile,
+                entity_count: count_entities(&entry_path),
+                encrypted: encryption::is_encrypted(&entry_path),
+                size: metadata.len(),
+                modified: OffsetDateTime::from(metadata.modified()?),
+                name,
+            });
+        }
+    }
+
+    tmp_vec.sort_by(|a, b| a.name.cmp(&b.name));
+    let cmfs = item::ComfyStruct { data: tmp_vec };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+fn rename_profile(old_name: &str, new_name: &str) -> io::Result<()> {
+    if !profile_exists(old_name) {
+        println!(
+            "profile `{}` does not exist. Nothing got renamed!",
+            old_name
+        );
+        return Ok(());
+    }
+    if profile_exists(new_name) {
+        println!(
+            "profile `{}` already exists. Nothing got renamed!",
+            new_name
+        );
+        return Ok(());
+    }
+
+    let old_backup_dir = backup_dir(old_name);
+    fs::rename(db_path(old_name), db_path(new_name))?;
+    if attachment_dir(old_name).exists() {
+        fs::rename(attachment_dir(old_name), attachment_dir(new_name))?;
+    }
+    let new_backup_dir = backup_dir(new_name);
+    if old_backup_dir.exists() && !new_backup_dir.exists() {
+        fs::rename(old_backup_dir, new_backup_dir)?;
+    }
+    if old_name == utils::get_default_profile() {
+        if let Err(e) = utils::set_default_profile(new_name) {
+            eprintln!(
+                "Could not make `{}` the default profile, error: {}",
+                new_name, e
+            );
+        }
+    }
+
+    println!("profile `{}` renamed to `{}`", old_name, new_name);
+
+    Ok(())
+}
+
+fn copy_profile(name: &str, new_name: &str) -> Result<(), StoreError> {
+    if !profile_exists(name) {
+        println!("profile `{}` does not exist. Nothing got copied!", name);
+        return Ok(());
+    }
+    if profile_exists(new_name) {
+        println!("profile `{}` already exists. Nothing got copied!", new_name);
+        return Ok(());
+    }
+
+    // The online backup reads a consistent snapshot, encrypted profiles can only be copied as
+    // they are since their passphrase is not known here
+    if encryption::is_encrypted(&db_path(name)) {
+        fs::copy(db_path(name), db_path(new_name))?;
+    } else {
+        let conn = Connection::open_with_flags(db_path(name), OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        conn.backup(DatabaseName::Main, db_path(new_name), None)?;
+    }
+
+    attachment::copy_store(&db_path(name), &db_path(new_name))?;
+
+    println!("profile `{}` copied to `{}`", name, new_name);
+
+    Ok(())
+}
+
+// Rotating backups are left alone so a deleted profile can still be restored
+fn delete_profile(name: &str) -> io::Result<()> {
+    fs::remove_file(db_path(name))?;
+    if attachment_dir(name).exists() {
+        fs::remove_dir_all(attachment_dir(name))?;
+    }
+
+    Ok(())
+}
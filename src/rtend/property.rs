@@ -0,0 +1,365 @@
+use rusqlite::{self, params, types::Value, Connection, OptionalExtension, ToSql};
+use std::{collections::BTreeSet, fmt, process, str::FromStr};
+use time::Date;
+
+use crate::item;
+use crate::item::ComfyTable;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Values are stored as they are typed, so SQLite compares numbers as numbers and ISO
+// dates as text. This turns them back into what the user typed
+pub const DISPLAY_VALUE: &str = "CASE property.type
+    WHEN 'bool' THEN (CASE property.value WHEN 1 THEN 'true' ELSE 'false' END)
+    ELSE cast(property.value as text) END";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PropertyType {
+    String,
+    Number,
+    Date,
+    Bool,
+}
+
+impl PropertyType {
+    // Picks the most specific type the value can be read as without changing it, so
+    // `01234` or `+123` stay strings instead of losing their leading zero or sign
+    pub fn infer(value: &str) -> Self {
+        [PropertyType::Bool, PropertyType::Number, PropertyType::Date]
+            .iter()
+            .find(|kind| match kind.parse_value(value) {
+                Some(Value::Integer(number)) if **kind == PropertyType::Number => {
+                    number.to_string() == value
+                }
+                Some(Value::Real(number)) => format!("{:?}", number) == value,
+                Some(Value::Text(text)) => text == value,
+                Some(_) => true,
+                None => false,
+            })
+            .copied()
+            .unwrap_or(PropertyType::String)
+    }
+
+    pub fn parse_value(self, value: &str) -> Option<Value> {
+        match self {
+            PropertyType::String => Some(Value::Text(value.to_string())),
+            PropertyType::Number => {
+                if let Ok(number) = i64::from_str(value) {
+                    Some(Value::Integer(number))
+                } else {
+                    f64::from_str(value)
+                        .ok()
+                        .filter(|number| number.is_finite())
+                        .map(Value::Real)
+                }
+            }
+            PropertyType::Date => Date::parse(value, DATE_FORMAT)
+                .ok()
+                .map(|date| Value::Text(date.format(DATE_FORMAT))),
+            PropertyType::Bool => match value {
+                "true" => Some(Value::Integer(1)),
+                "false" => Some(Value::Integer(0)),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl FromStr for PropertyType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(PropertyType::String),
+            "number" => Ok(PropertyType::Number),
+            "date" => Ok(PropertyType::Date),
+            "bool" => Ok(PropertyType::Bool),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for PropertyType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropertyType::String => write!(f, "string"),
+            PropertyType::Number => write!(f, "number"),
+            PropertyType::Date => write!(f, "date"),
+            PropertyType::Bool => write!(f, "bool"),
+        }
+    }
+}
+
+// A `key<op>value` condition of `find --property`
+pub struct Filter {
+    key: String,
+    operator: &'static str,
+    value: String,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let start = s
+            .find(&['=', '!', '<', '>'][..])
+            .ok_or_else(|| format!("`{}` has no operator, use one of = != < <= > >=", s))?;
+
+        // Longer operators first so `<=` isn't read as `<` followed by `=value`
+        let operator = ["!=", "<=", ">=", "=", "<", ">"]
+            .iter()
+            .find(|operator| s[start..].starts_with(*operator))
+            .ok_or_else(|| format!("`{}` has an unknown operator", s))?;
+
+        let key = s[..start].trim();
+        let value = s[start + operator.len()..].trim();
+        if key.is_empty() || value.is_empty() {
+            return Err(format!("`{}` must look like key=value", s));
+        }
+
+        Ok(Filter {
+            key: key.to_string(),
+            operator,
+            value: value.to_string(),
+        })
+    }
+}
+
+pub fn check_key(key: &str) -> Result<(), String> {
+    if key.trim().is_empty() || key.contains(&['=', '!', '<', '>'][..]) {
+        Err("property key must not be empty or contain any of = ! < >".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// Reads the value as the given type, or as whatever type fits it best
+pub fn get_value(value: &str, kind: Option<PropertyType>) -> (PropertyType, Value) {
+    let kind = kind.unwrap_or_else(|| PropertyType::infer(value));
+    match kind.parse_value(value) {
+        Some(parsed) => (kind, parsed),
+        None => {
+            eprintln!("`{}` is not a valid {}", value, kind);
+            process::exit(1);
+        }
+    }
+}
+
+pub fn add_property(
+    conn: Connection,
+    entity_id: u32,
+    key: &str,
+    kind: PropertyType,
+    value: Value,
+) -> rusqlite::Result<()> {
+    let existing_id: Option<u32> = conn
+        .query_row_and_then(
+            "SELECT id from property where entity_id = (?1) and key = (?2) and trash_id is null",
+            params![entity_id, key],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(existing_id) = existing_id {
+        println!(
+            "entity_id {} already has the property `{}` with property_id {}, use `edit --property` to change it. Nothing got added!",
+            entity_id, key, existing_id
+        );
+        return Ok(());
+    }
+
+    conn.execute(
+        "INSERT INTO property (entity_id, key, type, value) VALUES (?1, ?2, ?3, ?4)",
+        params![entity_id, key, kind.to_string(), value],
+    )?;
+
+    println!(
+        "{} property `{}` added to entity_id `{}`, its property_id is `{}`",
+        kind,
+        key,
+        entity_id,
+        conn.last_insert_rowid()
+    );
+
+    Ok(())
+}
+
+// Ids of the live entities having a property which satisfies the filter
+fn find_entities(conn: &Connection, filter: &Filter) -> rusqlite::Result<BTreeSet<u32>> {
+    let mut stmt =
+        conn.prepare("SELECT distinct type from property where key = (?) and trash_id is null")?;
+    let kinds = stmt
+        .query_map(params![filter.key], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // The same key can hold different types on different entities, the value is compared
+    // against each of them it can be read as
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    for kind in kinds {
+        if let Some(value) = PropertyType::from_str(&kind)
+            .ok()
+            .and_then(|kind| kind.parse_value(&filter.value))
+        {
+            conditions.push(format!("(type = ? and value {} ?)", filter.operator));
+            values.push(Value::Text(kind));
+            values.push(value);
+        }
+    }
+    if conditions.is_empty() {
+        return Ok(BTreeSet::new());
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT entity_id from property where key = ? and trash_id is null and ({})",
+        conditions.join(" or ")
+    ))?;
+    let mut query_params: Vec<&dyn ToSql> = vec![&filter.key];
+    query_params.extend(values.iter().map(|value| value as &dyn ToSql));
+
+    let entity_ids = stmt
+        .query_map(query_params, |row| row.get(0))?
+        .collect::<rusqlite::Result<BTreeSet<u32>>>()?;
+
+    Ok(entity_ids)
+}
+
+// Every filter has to match, the matched properties are shown along with the entity
+pub fn find_property(conn: Connection, filters: &[Filter]) -> rusqlite::Result<()> {
+    let mut entity_ids: Option<BTreeSet<u32>> = None;
+    for filter in filters {
+        let found = find_entities(&conn, filter)?;
+        entity_ids = Some(match entity_ids {
+            Some(entity_ids) => entity_ids.intersection(&found).copied().collect(),
+            None => found,
+        });
+    }
+
+    let keys: Vec<&str> = filters.iter().map(|filter| filter.key.as_str()).collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT entity.id,
+        (SELECT group_concat(name, '; ') from alias where entity_id = entity.id and trash_id is null) as alias_list,
+        (SELECT group_concat(key || ' = ' || {}, '; ') from property
+        where entity_id = entity.id and trash_id is null and key in ({})) as property_list,
+        entity.created from entity where entity.id = ?",
+        DISPLAY_VALUE,
+        vec!["?"; keys.len()].join(", ")
+    ))?;
+
+    let mut tmp_vec = Vec::new();
+    for entity_id in entity_ids.unwrap_or_default() {
+        let mut query_params: Vec<&dyn ToSql> = keys.iter().map(|key| key as &dyn ToSql).collect();
+        query_params.push(&entity_id);
+
+        tmp_vec.push(stmt.query_row(query_params, |row| {
+            Ok(item::PropertyFound {
+                entity_id: row.get(0)?,
+                alias_list: row.get(1).unwrap_or_else(|_| "".to_string()),
+                property_list: row.get(2)?,
+                created: row.get(3)?,
+            })
+        })?);
+    }
+    let cmfs = item::ComfyStruct { data: tmp_vec };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+pub fn list_properties(conn: &Connection, entity_id: u32) -> rusqlite::Result<Vec<item::Property>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, key, {}, type, updated from property
+        where entity_id = (?) and trash_id is null order by key",
+        DISPLAY_VALUE
+    ))?;
+
+    let property_iter = stmt.query_map(params![entity_id], |row| {
+        Ok(item::Property {
+            id: row.get(0)?,
+            key: row.get(1)?,
+            value: row.get(2)?,
+            property_type: row.get(3)?,
+            updated: row.get(4)?,
+        })
+    })?;
+
+    property_iter.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, PropertyType};
+    use rusqlite::types::Value;
+    use std::str::FromStr;
+
+    fn parts(filter: &str) -> (String, &'static str, String) {
+        let filter = Filter::from_str(filter).unwrap();
+        (filter.key, filter.operator, filter.value)
+    }
+
+    #[test]
+    fn filters_are_split_at_the_operator() {
+        assert_eq!(
+            parts("zip=01234"),
+            ("zip".to_string(), "=", "01234".to_string())
+        );
+        assert_eq!(
+            parts(" age >= 30 "),
+            ("age".to_string(), ">=", "30".to_string())
+        );
+        assert_eq!(
+            parts("age<=30"),
+            ("age".to_string(), "<=", "30".to_string())
+        );
+        assert_eq!(parts("age<30"), ("age".to_string(), "<", "30".to_string()));
+        assert_eq!(parts("age>30"), ("age".to_string(), ">", "30".to_string()));
+        assert_eq!(
+            parts("done!=true"),
+            ("done".to_string(), "!=", "true".to_string())
+        );
+    }
+
+    #[test]
+    fn only_the_first_operator_is_read() {
+        assert_eq!(parts("eq=a=b"), ("eq".to_string(), "=", "a=b".to_string()));
+        assert_eq!(
+            parts("cmp>=<x"),
+            ("cmp".to_string(), ">=", "<x".to_string())
+        );
+        assert_eq!(parts("zip=>1"), ("zip".to_string(), "=", ">1".to_string()));
+    }
+
+    #[test]
+    fn incomplete_filters_are_rejected() {
+        for filter in &["zip", "=01234", "zip=", " = ", "zip!01234"] {
+            assert!(Filter::from_str(filter).is_err(), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn values_get_the_most_specific_type() {
+        assert!(PropertyType::infer("true") == PropertyType::Bool);
+        assert!(PropertyType::infer("1234") == PropertyType::Number);
+        assert!(PropertyType::infer("-12") == PropertyType::Number);
+        assert!(PropertyType::infer("1.5") == PropertyType::Number);
+        assert!(PropertyType::infer("100.0") == PropertyType::Number);
+        assert!(PropertyType::infer("false") == PropertyType::Bool);
+        assert!(PropertyType::infer("inf") == PropertyType::String);
+        assert!(PropertyType::infer("1990-02-03") == PropertyType::Date);
+        assert!(PropertyType::infer("1990-02-30") == PropertyType::String);
+    }
+
+    #[test]
+    fn values_that_would_change_stay_strings() {
+        for value in &["01234", "+123", "-0", "1.50", "1e3", ".5", "00"] {
+            assert!(
+                PropertyType::infer(value) == PropertyType::String,
+                "{}",
+                value
+            );
+        }
+        assert_eq!(
+            PropertyType::String.parse_value("01234"),
+            Some(Value::Text("01234".to_string()))
+        );
+    }
+}
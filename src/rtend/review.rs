@@ -0,0 +1,253 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, Connection};
+use std::io::{self, Write};
+use std::{process, str::FromStr};
+
+// Ease every card starts with and the lowest it can drop to, as in SM-2
+const DEFAULT_EASE: f64 = 2.5;
+const MIN_EASE: f64 = 1.3;
+
+struct Card {
+    snippet_id: u32,
+    entity_id: u32,
+    alias_list: String,
+    data: String,
+    ease: f64,
+    interval: u32,
+    repetitions: u32,
+}
+
+enum Answer {
+    Grade(u32),
+    Skip,
+    Quit,
+}
+
+pub fn review(args: &ArgMatches, conn: Connection) {
+    let limit = args.value_of("limit").map(|limit| {
+        u32::from_str(limit).unwrap_or_else(|_err| {
+            eprintln!("count must be an u32");
+            process::exit(1);
+        })
+    });
+
+    match review_due(conn, limit) {
+        Ok(()) => (),
+        Err(e) => {
+            eprintln!("Could not review snippets, error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+// Snippets that were never reviewed are due right away, the ones that are overdue the
+// longest come first
+fn get_due_cards(conn: &Connection, limit: Option<u32>) -> rusqlite::Result<Vec<Card>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.id, s.entity_id,
+        (SELECT group_concat(name, '; ') from alias where entity_id = s.entity_id and trash_id is null) as alias_list,
+        s.data, coalesce(r.ease, (?1)), coalesce(r.interval, 0), coalesce(r.repetitions, 0)
+        from snippet s left join review r on r.snippet_id = s.id
+        where s.trash_id is null and (r.due is null or r.due <= date('now', 'localtime'))
+        order by r.due is null, r.due, s.id limit (?2)",
+    )?;
+
+    let card_iter = stmt.query_map(
+        params![DEFAULT_EASE, limit.map(i64::from).unwrap_or(-1)],
+        |row| {
+            Ok(Card {
+                snippet_id: row.get(0)?,
+                entity_id: row.get(1)?,
+                alias_list: row.get(2).unwrap_or_else(|_| "".to_string()),
+                data: row.get(3)?,
+                ease: row.get(4)?,
+                interval: row.get(5)?,
+                repetitions: row.get(6)?,
+            })
+        },
+    )?;
+
+    card_iter.collect()
+}
+
+pub fn count_due(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.query_row_and_then(
+        "SELECT count(*) from snippet s left join review r on r.snippet_id = s.id
+        where s.trash_id is null and (r.due is null or r.due <= date('now', 'localtime'))",
+        params![],
+        |row| row.get(0),
+    )
+}
+
+fn read_line() -> Option<String> {
+    let mut input = String::new();
+    match io::stdin().read_line(&mut input) {
+        Ok(0) => None,
+        Ok(_) => Some(input.trim().to_string()),
+        Err(e) => {
+            eprintln!("Could not read stdin, error: {}", e);
+            None
+        }
+    }
+}
+
+fn get_answer() -> Answer {
+    loop {
+        print!("How well did you remember it? [0-5], [s]kip or [q]uit: ");
+        io::stdout().flush().unwrap();
+
+        match read_line().as_deref() {
+            None | Some("q") | Some("quit") => return Answer::Quit,
+            Some("s") | Some("skip") => return Answer::Skip,
+            Some(input) => match u32::from_str(input) {
+                Ok(grade) if grade <= 5 => return Answer::Grade(grade),
+                _ => println!("0 means you did not remember it at all, 5 means perfectly"),
+            },
+        }
+    }
+}
+
+// SM-2: a failed recall starts the card over, a successful one grows the interval by the
+// ease, which itself goes up or down with how easy the recall was
+fn schedule(card: &Card, grade: u32) -> (f64, u32, u32) {
+    let (interval, repetitions) = if grade >= 3 {
+        let interval = match card.repetitions {
+            0 => 1,
+            1 => 6,
+            _ => (f64::from(card.interval) * card.ease).round() as u32,
+        };
+        (interval, card.repetitions + 1)
+    } else {
+        (1, 0)
+    };
+
+    let lapse = f64::from(5 - grade);
+    let ease = (card.ease + 0.1 - lapse * (0.08 + lapse * 0.02)).max(MIN_EASE);
+
+    (ease, interval, repetitions)
+}
+
+fn save_review(conn: &Connection, card: &Card, grade: u32) -> rusqlite::Result<u32> {
+    let (ease, interval, repetitions) = schedule(card, grade);
+    conn.execute(
+        "INSERT INTO review (snippet_id, ease, interval, repetitions, due, last_reviewed)
+        VALUES (?1, ?2, ?3, ?4, date('now', 'localtime', (?5)), datetime('now'))
+        ON CONFLICT (snippet_id) DO UPDATE SET ease = excluded.ease, interval = excluded.interval,
+        repetitions = excluded.repetitions, due = excluded.due, last_reviewed = excluded.last_reviewed",
+        params![
+            card.snippet_id,
+            ease,
+            interval,
+            repetitions,
+            format!("+{} days", interval)
+        ],
+    )?;
+
+    Ok(interval)
+}
+
+fn review_due(conn: Connection, limit: Option<u32>) -> rusqlite::Result<()> {
+    let cards = get_due_cards(&conn, limit)?;
+    if cards.is_empty() {
+        println!("Nothing is due for review.");
+        return Ok(());
+    }
+
+    let mut reviewed = 0;
+    for (index, card) in cards.iter().enumerate() {
+        println!("{}", "-".repeat(40));
+        println!(
+            "[{}/{}] snippet id {} of entity_id {}: {}",
+            index + 1,
+            cards.len(),
+            card.snippet_id,
+            card.entity_id,
+            card.alias_list
+        );
+        print!("Try to recall it, then press Return to show the snippet or [q]uit: ");
+        io::stdout().flush().unwrap();
+        match read_line().as_deref() {
+            None | Some("q") | Some("quit") => break,
+            _ => (),
+        }
+        println!("{}", card.data);
+
+        match get_answer() {
+            Answer::Grade(grade) => {
+                let interval = save_review(&conn, card, grade)?;
+                reviewed += 1;
+                match interval {
+                    1 => println!("next review tomorrow"),
+                    _ => println!("next review in {} days", interval),
+                }
+            }
+            Answer::Skip => (),
+            Answer::Quit => break,
+        }
+    }
+
+    println!("{}", "-".repeat(40));
+    println!(
+        "{} snippets reviewed, {} still due",
+        reviewed,
+        count_due(&conn)?
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{schedule, Card, DEFAULT_EASE, MIN_EASE};
+
+    fn card(ease: f64, interval: u32, repetitions: u32) -> Card {
+        Card {
+            snippet_id: 1,
+            entity_id: 1,
+            alias_list: String::new(),
+            data: String::new(),
+            ease,
+            interval,
+            repetitions,
+        }
+    }
+
+    #[test]
+    fn recalled_cards_grow_their_interval() {
+        let (ease, interval, repetitions) = schedule(&card(DEFAULT_EASE, 0, 0), 4);
+        assert_eq!((interval, repetitions), (1, 1));
+        assert!((ease - DEFAULT_EASE).abs() < 1e-9);
+
+        let (_, interval, repetitions) = schedule(&card(DEFAULT_EASE, 1, 1), 4);
+        assert_eq!((interval, repetitions), (6, 2));
+
+        let (_, interval, repetitions) = schedule(&card(DEFAULT_EASE, 6, 2), 4);
+        assert_eq!((interval, repetitions), (15, 3));
+    }
+
+    #[test]
+    fn ease_follows_the_grade() {
+        let (ease, _, _) = schedule(&card(DEFAULT_EASE, 6, 2), 5);
+        assert!((ease - 2.6).abs() < 1e-9);
+
+        let (ease, _, _) = schedule(&card(DEFAULT_EASE, 6, 2), 3);
+        assert!((ease - 2.36).abs() < 1e-9);
+    }
+
+    #[test]
+    fn failed_cards_start_over() {
+        for grade in 0..3 {
+            let (_, interval, repetitions) = schedule(&card(DEFAULT_EASE, 15, 3), grade);
+            assert_eq!((interval, repetitions), (1, 0), "{}", grade);
+        }
+    }
+
+    #[test]
+    fn ease_never_drops_below_the_floor() {
+        let (ease, _, _) = schedule(&card(MIN_EASE, 6, 2), 0);
+        assert!((ease - MIN_EASE).abs() < 1e-9);
+
+        let (ease, _, _) = schedule(&card(1.4, 6, 2), 0);
+        assert!((ease - MIN_EASE).abs() < 1e-9);
+    }
+}
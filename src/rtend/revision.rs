@@ -0,0 +1,74 @@
+use rusqlite::{params, Connection};
+use similar::TextDiff;
+
+use crate::item;
+
+// Both kinds of snippets keep their history the same way, only the tables differ
+#[derive(Clone, Copy)]
+pub enum SnippetKind {
+    Snippet,
+    RelationSnippet,
+}
+
+impl SnippetKind {
+    pub fn table(self) -> &'static str {
+        match self {
+            SnippetKind::Snippet => "snippet",
+            SnippetKind::RelationSnippet => "relation_snippet",
+        }
+    }
+}
+
+// Revisions start at 1, the current text is always the latest revision. Snippets in the trash
+// have none
+pub fn get_revisions(
+    conn: &Connection,
+    kind: SnippetKind,
+    id: u32,
+) -> rusqlite::Result<Vec<item::Revision>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT revision, data, created, 0 from {0}_revision where {0}_id = (?1)
+            and {0}_id in (SELECT id from {0} where trash_id is null)
+        UNION ALL
+        SELECT (SELECT count(*) + 1 from {0}_revision where {0}_id = (?1)), data, updated, 1
+        from {0} where id = (?1) and trash_id is null
+        order by 1",
+        kind.table()
+    ))?;
+
+    let revision_iter = stmt.query_map(params![id], |row| {
+        Ok(item::Revision {
+            revision: row.get(0)?,
+            data: row.get(1)?,
+            created: row.get(2)?,
+            current: row.get(3)?,
+        })
+    })?;
+
+    let mut tmp_vec = Vec::new();
+    for revision in revision_iter {
+        tmp_vec.push(revision?);
+    }
+
+    Ok(tmp_vec)
+}
+
+pub fn get_revision(
+    conn: &Connection,
+    kind: SnippetKind,
+    id: u32,
+    revision: u32,
+) -> rusqlite::Result<Option<String>> {
+    Ok(get_revisions(conn, kind, id)?
+        .into_iter()
+        .find(|rev| rev.revision == revision)
+        .map(|rev| rev.data))
+}
+
+pub fn unified_diff(old: &str, new: &str, old_header: &str, new_header: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_header, new_header)
+        .to_string()
+}
@@ -9,10 +9,10 @@ use skim::prelude::*;
 use skim::Skim;
 use std::{env, path, process};
 
+use crate::config;
+use crate::encryption;
 use crate::item;
-
-const DEFAULT_DATABSE: &str = "notes";
-const DEFAULT_PREVIEW_COMMAND: &str = "list --entity {2} -vv";
+use crate::utils;
 
 struct RtendSkimItem {
     inner: String,
@@ -31,11 +31,11 @@ impl SkimItem for RtendSkimItem {
 fn make_rsi(conn: rusqlite::Connection, term_width: u16) -> RtendSkimItem {
     let mut stmt = conn.prepare("
         SELECT id,
-        (SELECT substr(group_concat(name, '; '), 0, 1000) from alias where entity_id = entity.id limit 4) as alias_list,
-        (SELECT count(*) from alias where entity_id = entity.id) as alias_count,
-        (SELECT count(*) from snippet where entity_id = entity.id) as snippet_count,
+        (SELECT substr(group_concat(name, '; '), 0, 1000) from alias where entity_id = entity.id and trash_id is null limit 4) as alias_list,
+        (SELECT count(*) from alias where entity_id = entity.id and trash_id is null) as alias_count,
+        (SELECT count(*) from snippet where entity_id = entity.id and trash_id is null) as snippet_count,
         created
-        from entity order by 1
+        from entity where trash_id is null order by 1
         ").expect("Could not prepare stmt");
 
     let entity_iter = stmt
@@ -72,7 +72,7 @@ fn make_rsi(conn: rusqlite::Connection, term_width: u16) -> RtendSkimItem {
             Cell::new(&tmp.alias_list),
             Cell::new(&tmp.alias_count),
             Cell::new(&tmp.snippet_count),
-            Cell::new(&tmp.created.format(time::Format::Rfc3339)),
+            Cell::new(&config::format_time(tmp.created)),
         ]);
     }
 
@@ -81,16 +81,50 @@ fn make_rsi(conn: rusqlite::Connection, term_width: u16) -> RtendSkimItem {
     }
 }
 
-pub fn skim(args: &ArgMatches, term_width: u16, conn: rusqlite::Connection) {
+pub fn skim(
+    args: &ArgMatches,
+    term_width: u16,
+    conn: rusqlite::Connection,
+    passphrase: Option<&str>,
+) {
     let full_preview_command: String;
     let exe_path: path::PathBuf;
-    let mut db: &str = DEFAULT_DATABSE;
-    let mut preview_command: String = DEFAULT_PREVIEW_COMMAND.to_string();
-
-    if args.is_present("profile") {
-        db = args.value_of("profile").unwrap();
-        preview_command = format!("--profile {} {}", db, DEFAULT_PREVIEW_COMMAND);
+    let db = utils::get_db_path(&conn).unwrap_or_else(|err| {
+        eprintln!("Could not get the path of the database, error: {}", err);
+        process::exit(1);
+    });
+    let config = config::get();
+    let mut preview_command = args
+        .value_of("preview")
+        .or_else(|| config.preview_command.as_deref())
+        .unwrap_or(config::DEFAULT_PREVIEW_COMMAND)
+        .to_string();
+    let preview_window = args
+        .value_of("preview_window")
+        .or_else(|| config.preview_window.as_deref())
+        .unwrap_or(config::DEFAULT_PREVIEW_WINDOW);
+
+    // The preview and the final listing are separate processes, they need to open the same
+    // database and get the overrides given on the command line as well
+    let mut global_args = vec!["--db".to_string(), db.to_string_lossy().into_owned()];
+    for (flag, key) in &[
+        ("--table-preset", "table_preset"),
+        ("--time-format", "time_format"),
+    ] {
+        if let Some(value) = args.value_of(key) {
+            global_args.push(flag.to_string());
+            global_args.push(value.to_string());
+        }
     }
+    preview_command = format!(
+        "{} {}",
+        global_args
+            .iter()
+            .map(|arg| format!("'{}'", arg.replace('\'', "'\\''")))
+            .collect::<Vec<_>>()
+            .join(" "),
+        preview_command
+    );
 
     match env::current_exe() {
         Ok(path) => {
@@ -105,7 +139,7 @@ pub fn skim(args: &ArgMatches, term_width: u16, conn: rusqlite::Connection) {
 
     let options = SkimOptionsBuilder::default()
         .preview(Some(&full_preview_command))
-        .preview_window(Some("down:50%"))
+        .preview_window(Some(preview_window))
         .multi(false)
         .header_lines(2)
         .tabstop(Some("4"))
@@ -120,9 +154,15 @@ pub fn skim(args: &ArgMatches, term_width: u16, conn: rusqlite::Connection) {
     }
     drop(tx_item);
 
+    // Previews of an encrypted profile need its passphrase, it is only in the environment while
+    // skim runs so nothing else rtend spawns gets it
+    if let Some(passphrase) = passphrase {
+        env::set_var(encryption::PASSPHRASE_ENV, passphrase);
+    }
     let selected_items = Skim::run_with(&options, Some(rx_item))
         .map(|out| out.selected_items)
         .unwrap_or_else(|| Vec::new());
+    env::remove_var(encryption::PASSPHRASE_ENV);
 
     let entry_selected: String;
     let item = selected_items.get(0);
@@ -149,13 +189,15 @@ pub fn skim(args: &ArgMatches, term_width: u16, conn: rusqlite::Connection) {
         }
     };
 
-    process::Command::new(&exe_path)
-        .arg("--profile")
-        .arg(db)
+    let mut list_command = process::Command::new(&exe_path);
+    list_command
+        .args(&global_args)
         .arg("list")
         .arg("-e")
         .arg(entity_id)
-        .arg("-vv")
-        .status()
-        .expect("Could not display result");
+        .arg("-vv");
+    if let Some(passphrase) = passphrase {
+        list_command.env(encryption::PASSPHRASE_ENV, passphrase);
+    }
+    list_command.status().expect("Could not display result");
 }
@@ -0,0 +1,29 @@
+use std::{fmt, str::FromStr};
+
+// Things that can be tagged
+#[derive(Clone, Copy)]
+pub enum TagKind {
+    Entity,
+    Snippet,
+}
+
+impl FromStr for TagKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "e" | "entity" => Ok(TagKind::Entity),
+            "s" | "snippet" => Ok(TagKind::Snippet),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for TagKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TagKind::Entity => write!(f, "entity"),
+            TagKind::Snippet => write!(f, "snippet"),
+        }
+    }
+}
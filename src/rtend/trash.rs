@@ -0,0 +1,334 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, Connection, OptionalExtension};
+use std::{fmt, process, str::FromStr, unreachable};
+
+use crate::attachment;
+use crate::item;
+use crate::item::ComfyTable;
+use crate::property;
+use crate::utils;
+
+// Tables that are moved to the trash instead of being deleted right away
+const TRASHABLE_TABLES: &[&str] = &[
+    "entity",
+    "alias",
+    "snippet",
+    "relation",
+    "relation_snippet",
+    "attachment",
+    "property",
+];
+
+#[derive(Clone, Copy)]
+pub enum TrashKind {
+    Entity,
+    Alias,
+    Snippet,
+    Relation,
+    RelationSnippet,
+    Attachment,
+    Property,
+}
+
+impl TrashKind {
+    fn table(self) -> &'static str {
+        match self {
+            TrashKind::Entity => "entity",
+            TrashKind::Alias => "alias",
+            TrashKind::Snippet => "snippet",
+            TrashKind::Relation => "relation",
+            TrashKind::RelationSnippet => "relation_snippet",
+            TrashKind::Attachment => "attachment",
+            TrashKind::Property => "property",
+        }
+    }
+}
+
+impl FromStr for TrashKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "entity" => Ok(TrashKind::Entity),
+            "alias" => Ok(TrashKind::Alias),
+            "snippet" => Ok(TrashKind::Snippet),
+            "relation" => Ok(TrashKind::Relation),
+            "relation_snippet" | "relation-snippet" => Ok(TrashKind::RelationSnippet),
+            "attachment" => Ok(TrashKind::Attachment),
+            "property" => Ok(TrashKind::Property),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for TrashKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.table().replace('_', " "))
+    }
+}
+
+pub fn trash(args: &ArgMatches, conn: Connection) {
+    match args.subcommand() {
+        ("list", Some(_list_matches)) => match list_trash(conn) {
+            Ok(()) => (),
+            Err(e) => {
+                eprintln!("Could not list trash, error: {}", e);
+                process::exit(1);
+            }
+        },
+
+        ("restore", Some(restore_matches)) => {
+            let kind = TrashKind::from_str(restore_matches.value_of("kind").unwrap()).unwrap();
+            let id =
+                u32::from_str(restore_matches.value_of("id").unwrap()).unwrap_or_else(|_err| {
+                    eprintln!("id must be an u32");
+                    process::exit(1);
+                });
+
+            match restore(conn, kind, id) {
+                Ok(()) => (),
+                Err(e) => {
+                    eprintln!("Could not restore {}, error: {}", kind, e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        ("empty", Some(empty_matches)) => {
+            let older_than = empty_matches.value_of("older_than").map(|days| {
+                u32::from_str(days).unwrap_or_else(|_err| {
+                    eprintln!("days must be an u32");
+                    process::exit(1);
+                })
+            });
+
+            if older_than.is_none() {
+                println!("This operation will permanently delete everything in the trash");
+                if !utils::get_yn_input().unwrap() {
+                    println!("Aborted");
+                    process::exit(1);
+                }
+            }
+
+            match empty_trash(conn, older_than) {
+                Ok(()) => (),
+                Err(e) => {
+                    eprintln!("Could not empty trash, error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        _ => unreachable!(),
+    }
+}
+
+// Moves a single row to the trash, returns how many rows were moved
+pub fn trash_item(conn: &mut Connection, kind: TrashKind, id: u32) -> rusqlite::Result<usize> {
+    let tx = conn.transaction()?;
+    let trash_id = new_trash_entry(&tx, kind, id)?;
+    let rows_returned = tx.execute(
+        &format!(
+            "UPDATE {} set trash_id = (?1) where id = (?2) and trash_id is null",
+            kind.table()
+        ),
+        params![trash_id, id],
+    )?;
+
+    // Nothing got trashed, the transaction is rolled back when dropped
+    if rows_returned == 1 {
+        tx.commit()?;
+    }
+
+    Ok(rows_returned)
+}
+
+pub fn new_trash_entry(conn: &Connection, kind: TrashKind, id: u32) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO trash (kind, item_id) VALUES (?1, ?2)",
+        params![kind.table(), id],
+    )?;
+
+    Ok(conn.last_insert_rowid())
+}
+
+fn list_trash(conn: Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, kind, item_id,
+        coalesce(CASE kind
+            WHEN 'entity' THEN (SELECT group_concat(name, '; ') from alias where entity_id = item_id)
+            WHEN 'alias' THEN (SELECT name from alias where id = item_id)
+            WHEN 'snippet' THEN (SELECT data from snippet where id = item_id)
+            WHEN 'relation' THEN (SELECT entity_id_a || ' | ' || entity_id_b from relation where id = item_id)
+            WHEN 'relation_snippet' THEN (SELECT data from relation_snippet where id = item_id)
+            WHEN 'attachment' THEN (SELECT name from attachment where id = item_id)
+            WHEN 'property' THEN (SELECT key || ' = ' || {} from property where id = item_id)
+        END, '') as summary,
+        (SELECT count(*) from entity where trash_id = trash.id)
+        + (SELECT count(*) from alias where trash_id = trash.id)
+        + (SELECT count(*) from snippet where trash_id = trash.id)
+        + (SELECT count(*) from relation where trash_id = trash.id)
+        + (SELECT count(*) from relation_snippet where trash_id = trash.id)
+        + (SELECT count(*) from attachment where trash_id = trash.id)
+        + (SELECT count(*) from property where trash_id = trash.id) as item_count,
+        created from trash order by id",
+        property::DISPLAY_VALUE
+    ))?;
+
+    let trash_iter = stmt.query_map(params![], |row| {
+        Ok(item::TrashEntry {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            item_id: row.get(2)?,
+            summary: row.get(3)?,
+            item_count: row.get(4)?,
+            created: row.get(5)?,
+        })
+    })?;
+
+    let mut tmp_vec = Vec::new();
+    for entry in trash_iter {
+        tmp_vec.push(entry.unwrap());
+    }
+    let cmfs = item::ComfyStruct { data: tmp_vec };
+    cmfs.print_comfy_table();
+
+    Ok(())
+}
+
+// Counts live things which belong to something that is still in the trash
+fn count_orphans(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.query_row_and_then(
+        "SELECT (SELECT count(*) from alias where trash_id is null
+            and entity_id in (SELECT id from entity where trash_id is not null))
+        + (SELECT count(*) from snippet where trash_id is null
+            and entity_id in (SELECT id from entity where trash_id is not null))
+        + (SELECT count(*) from relation where trash_id is null
+            and (entity_id_a in (SELECT id from entity where trash_id is not null)
+            or entity_id_b in (SELECT id from entity where trash_id is not null)))
+        + (SELECT count(*) from relation_snippet where trash_id is null
+            and relation_id in (SELECT id from relation where trash_id is not null))
+        + (SELECT count(*) from attachment where trash_id is null
+            and entity_id in (SELECT id from entity where trash_id is not null))
+        + (SELECT count(*) from property where trash_id is null
+            and entity_id in (SELECT id from entity where trash_id is not null))",
+        params![],
+        |row| row.get(0),
+    )
+}
+
+fn restore(mut conn: Connection, kind: TrashKind, id: u32) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+
+    let trash_id: Option<i64> = tx
+        .query_row_and_then(
+            &format!("SELECT trash_id from {} where id = (?)", kind.table()),
+            params![id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+
+    let trash_id = match trash_id {
+        Some(trash_id) => trash_id,
+        None => {
+            println!(
+                "{} id {} is not in the trash. Nothing got restored!",
+                kind, id
+            );
+            return Ok(());
+        }
+    };
+
+    // A key can only be live once per entity, so a property added again since blocks the restore
+    let live_property: Option<(u32, String)> = tx
+        .query_row_and_then(
+            "SELECT live.id, live.key from property as trashed
+            join property as live on live.entity_id = trashed.entity_id and live.key = trashed.key
+            where trashed.trash_id = (?) and live.trash_id is null
+            order by live.id limit 1",
+            params![trash_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    if let Some((live_id, key)) = live_property {
+        println!(
+            "{} id {} comes with the property key `{}`, which property id {} uses now, delete that first. Nothing got restored!",
+            kind, id, key, live_id
+        );
+        return Ok(());
+    }
+
+    // Everything that was deleted together is restored together
+    let mut rows_returned = 0;
+    for table in TRASHABLE_TABLES {
+        rows_returned += tx.execute(
+            &format!("UPDATE {} set trash_id = null where trash_id = (?)", table),
+            params![trash_id],
+        )?;
+    }
+
+    if count_orphans(&tx)? > 0 {
+        println!(
+            "{} id {} belongs to something that is still in the trash, restore that first. Nothing got restored!",
+            kind, id
+        );
+        return Ok(());
+    }
+
+    tx.execute("DELETE from trash where id = (?)", params![trash_id])?;
+    tx.commit()?;
+
+    println!(
+        "{} id `{}` restored along with {} other things",
+        kind,
+        id,
+        rows_returned - 1
+    );
+
+    Ok(())
+}
+
+fn empty_trash(mut conn: Connection, older_than: Option<u32>) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let modifier = older_than.map(|days| format!("-{} days", days));
+
+    // Children go first, anything left is removed by the foreign key cascades
+    let mut rows_returned = 0;
+    for table in TRASHABLE_TABLES.iter().rev() {
+        rows_returned += tx.execute(
+            &format!(
+                "DELETE from {} where trash_id in
+                (SELECT id from trash where (?1) is null or created <= datetime('now', (?1)))",
+                table
+            ),
+            params![modifier],
+        )?;
+    }
+
+    // Entries whose things are all gone, including the ones taken away by the cascades
+    let entries_returned = tx.execute(
+        "DELETE from trash where id not in (SELECT trash_id from entity where trash_id is not null)
+        and id not in (SELECT trash_id from alias where trash_id is not null)
+        and id not in (SELECT trash_id from snippet where trash_id is not null)
+        and id not in (SELECT trash_id from relation where trash_id is not null)
+        and id not in (SELECT trash_id from relation_snippet where trash_id is not null)
+        and id not in (SELECT trash_id from attachment where trash_id is not null)
+        and id not in (SELECT trash_id from property where trash_id is not null)",
+        params![],
+    )?;
+    tx.commit()?;
+
+    // Stored files stay around as long as something else still has the same contents attached
+    if let Err(e) = attachment::remove_unreferenced_files(&conn) {
+        eprintln!("Could not remove unused attachment files, error: {}", e);
+    }
+
+    println!(
+        "{} trash entries emptied, {} things permanently deleted",
+        entries_returned, rows_returned
+    );
+
+    Ok(())
+}
@@ -0,0 +1,376 @@
+use clap::ArgMatches;
+use rusqlite::{self, params, types::Value, Connection, OptionalExtension, ToSql, Transaction};
+use std::{fmt, process, str::FromStr};
+
+use crate::attachment;
+use crate::journal;
+use crate::link;
+use crate::revision::SnippetKind;
+
+#[derive(Debug)]
+pub enum UndoError {
+    // The row a change was made to is not the way the journal left it
+    Conflict(String),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for UndoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UndoError::Conflict(msg) => write!(f, "{}", msg),
+            UndoError::Sqlite(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for UndoError {
+    fn from(e: rusqlite::Error) -> Self {
+        UndoError::Sqlite(e)
+    }
+}
+
+struct Change {
+    operation: String,
+    kind: String,
+    row_id: i64,
+    old_value: Option<String>,
+    new_value: Option<String>,
+}
+
+fn get_count(args: &ArgMatches) -> u32 {
+    u32::from_str(args.value_of("count").unwrap()).unwrap_or_else(|_err| {
+        eprintln!("count must be an u32");
+        process::exit(1);
+    })
+}
+
+pub fn undo(args: &ArgMatches, mut conn: Connection) {
+    for _ in 0..get_count(args) {
+        match last_undoable(&conn) {
+            Ok(Some(command_id)) => match revert(&mut conn, command_id, "undo") {
+                Ok(()) => (),
+                Err(e) => {
+                    eprintln!("Could not undo, error: {}", e);
+                    process::exit(1);
+                }
+            },
+            Ok(None) => {
+                println!("Nothing left to undo.");
+                break;
+            }
+            Err(e) => {
+                eprintln!("Could not read the journal, error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+pub fn redo(args: &ArgMatches, mut conn: Connection) {
+    for _ in 0..get_count(args) {
+        match last_redoable(&conn) {
+            Ok(Some(command_id)) => match revert(&mut conn, command_id, "redo") {
+                Ok(()) => (),
+                Err(e) => {
+                    eprintln!("Could not redo, error: {}", e);
+                    process::exit(1);
+                }
+            },
+            Ok(None) => {
+                println!("Nothing left to redo.");
+                break;
+            }
+            Err(e) => {
+                eprintln!("Could not read the journal, error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+// The latest command which changed something and wasn't undone yet, a redo counts as such
+fn last_undoable(conn: &Connection) -> rusqlite::Result<Option<u32>> {
+    conn.query_row_and_then(
+        "SELECT id from journal_command c where kind in ('do', 'redo') and not undone
+        and exists (SELECT 1 from journal where command_id = c.id)
+        order by id desc limit 1",
+        params![],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+// The latest undo which wasn't redone yet, as long as nothing got changed after it
+fn last_redoable(conn: &Connection) -> rusqlite::Result<Option<u32>> {
+    conn.query_row_and_then(
+        "SELECT id from journal_command c where kind = 'undo' and not undone
+        and not exists (SELECT 1 from journal_command later where later.id > c.id
+        and later.kind = 'do' and exists (SELECT 1 from journal where command_id = later.id))
+        order by id desc limit 1",
+        params![],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+// Only column names of the journaled tables ever end up in the journal
+fn check_name(name: &str) -> Result<(), UndoError> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+        Ok(())
+    } else {
+        Err(UndoError::Conflict(format!(
+            "`{}` in the journal is not a column name",
+            name
+        )))
+    }
+}
+
+fn read_row(tx: &Transaction, value: &Option<String>) -> Result<Vec<(String, Value)>, UndoError> {
+    let mut stmt = tx.prepare("SELECT key, value from json_each(?)")?;
+    let columns = stmt
+        .query_map(params![value], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, Value)>>>()?;
+    for (column, _) in &columns {
+        check_name(column)?;
+    }
+
+    Ok(columns)
+}
+
+fn changed_since(change: &Change) -> UndoError {
+    UndoError::Conflict(format!(
+        "{} id {} was changed since, nothing got reverted",
+        change.kind, change.row_id
+    ))
+}
+
+// The row has to be the way the change left it, or gone if the change deleted it, anything
+// else means it got changed later on and reverting would throw that away. Rows waiting to be
+// trashed again don't have their `trash_id` yet
+fn check_current(tx: &Transaction, change: &Change, trashed: bool) -> Result<(), UndoError> {
+    if change.operation == "delete" {
+        let count: u32 = tx.query_row_and_then(
+            &format!("SELECT count(*) from {} where rowid = (?)", change.kind),
+            params![change.row_id],
+            |row| row.get(0),
+        )?;
+        return match count {
+            0 => Ok(()),
+            _ => Err(changed_since(change)),
+        };
+    }
+
+    let mut columns = read_row(tx, &change.new_value)?;
+    if trashed {
+        columns.retain(|(column, _)| column != "trash_id");
+    }
+    // The journal keeps reals with 15 significant digits
+    let conditions: Vec<String> = columns
+        .iter()
+        .map(|(column, value)| match value {
+            Value::Real(_) => format!("abs({} - ?) < 1e-9", column),
+            _ => format!("{} is ?", column),
+        })
+        .collect();
+    let mut values: Vec<&dyn ToSql> = columns
+        .iter()
+        .map(|(_, value)| value as &dyn ToSql)
+        .collect();
+    values.push(&change.row_id);
+
+    let count: u32 = tx.query_row_and_then(
+        &format!(
+            "SELECT count(*) from {} where {} and rowid = ?",
+            change.kind,
+            conditions.join(" and ")
+        ),
+        values,
+        |row| row.get(0),
+    )?;
+    match count {
+        1 => Ok(()),
+        _ => Err(changed_since(change)),
+    }
+}
+
+// Applies the opposite of every change the command made, the latest change first
+fn revert(conn: &mut Connection, command_id: u32, kind: &str) -> Result<(), UndoError> {
+    let tx = conn.transaction()?;
+    // Rows come back in the opposite order they went away, their parents might come later
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+
+    let command: String = tx.query_row_and_then(
+        "SELECT command from journal_command where id = (?)",
+        params![command_id],
+        |row| row.get(0),
+    )?;
+    tx.execute(
+        "INSERT INTO journal_command (command, kind, reverts, user) VALUES (?1, ?2, ?3, ?4)",
+        params![command, kind, command_id, journal::user()],
+    )?;
+
+    let mut stmt = tx.prepare(
+        "SELECT operation, kind, row_id, old_value, new_value from journal
+        where command_id = (?) order by id desc",
+    )?;
+    let changes = stmt
+        .query_map(params![command_id], |row| {
+            Ok(Change {
+                operation: row.get(0)?,
+                kind: row.get(1)?,
+                row_id: row.get(2)?,
+                old_value: row.get(3)?,
+                new_value: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+
+    // Rows put back out of the trash are only trashed again once everything is back,
+    // nothing can be added to a trashed entity
+    let mut trashed = Vec::new();
+    for change in &changes {
+        if !journal::KINDS.contains(&change.kind.as_str()) {
+            return Err(UndoError::Conflict(format!(
+                "`{}` in the journal is not a kind of thing",
+                change.kind
+            )));
+        }
+        let pending = trashed
+            .iter()
+            .any(|(kind, row_id, _)| *kind == change.kind && *row_id == change.row_id);
+        check_current(&tx, change, pending)?;
+
+        match change.operation.as_str() {
+            "insert" => {
+                tx.execute(
+                    &format!("DELETE from {} where rowid = (?)", change.kind),
+                    params![change.row_id],
+                )?;
+            }
+            "update" => {
+                let columns = read_row(&tx, &change.old_value)?;
+                let assignments: Vec<String> = columns
+                    .iter()
+                    .map(|(column, _)| format!("{} = ?", column))
+                    .collect();
+                let mut values: Vec<&dyn ToSql> = columns
+                    .iter()
+                    .map(|(_, value)| value as &dyn ToSql)
+                    .collect();
+                values.push(&change.row_id);
+
+                tx.execute(
+                    &format!(
+                        "UPDATE {} set {} where rowid = ?",
+                        change.kind,
+                        assignments.join(", ")
+                    ),
+                    values,
+                )?;
+            }
+            "delete" => {
+                let mut columns = read_row(&tx, &change.old_value)?;
+                if let Some(index) = columns.iter().position(|(column, _)| column == "trash_id") {
+                    let (_, trash_id) = columns.remove(index);
+                    if trash_id != Value::Null {
+                        trashed.push((change.kind.clone(), change.row_id, trash_id));
+                    }
+                }
+                // Tables without an id column, like entity_tag, get their rowid back as well
+                let mut names: Vec<&str> = vec!["rowid"];
+                let mut values: Vec<&dyn ToSql> = vec![&change.row_id];
+                if columns.iter().any(|(column, _)| column == "id") {
+                    names.clear();
+                    values.clear();
+                }
+                for (column, value) in &columns {
+                    names.push(column);
+                    values.push(value);
+                }
+
+                tx.execute(
+                    &format!(
+                        "INSERT INTO {} ({}) VALUES ({})",
+                        change.kind,
+                        names.join(", "),
+                        vec!["?"; names.len()].join(", ")
+                    ),
+                    values,
+                )?;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    for (kind, row_id, trash_id) in trashed {
+        tx.execute(
+            &format!("UPDATE {} set trash_id = (?1) where rowid = (?2)", kind),
+            params![trash_id, row_id],
+        )?;
+    }
+
+    // Links aren't journaled, they are parsed again from the text that is back
+    for change in &changes {
+        let snippet_kind = match change.kind.as_str() {
+            "snippet" => SnippetKind::Snippet,
+            "relation_snippet" => SnippetKind::RelationSnippet,
+            _ => continue,
+        };
+        let data: Option<String> = tx
+            .query_row_and_then(
+                &format!("SELECT data from {} where id = (?)", change.kind),
+                params![change.row_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(data) = data {
+            link::update_links(&tx, snippet_kind, change.row_id as u32, &data)?;
+        }
+    }
+
+    tx.execute(
+        "UPDATE journal_command set undone = 1 where id = (?)",
+        params![command_id],
+    )?;
+    tx.commit()?;
+
+    match kind {
+        "undo" => println!("undid `{}`, {} changes reverted", command, changes.len()),
+        _ => println!(
+            "redid `{}`, {} changes applied again",
+            command,
+            changes.len()
+        ),
+    }
+    warn_missing_files(conn, &changes)?;
+
+    Ok(())
+}
+
+// Emptying the trash removes the files nothing refers to anymore, the attachments that are
+// back have no content then
+fn warn_missing_files(conn: &Connection, changes: &[Change]) -> Result<(), UndoError> {
+    for change in changes {
+        if change.kind != "attachment" || change.operation != "delete" {
+            continue;
+        }
+        let hash: Option<String> = conn
+            .query_row_and_then(
+                "SELECT hash from attachment where id = (?)",
+                params![change.row_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(hash) = hash {
+            if !attachment::is_stored(conn, &hash)? {
+                eprintln!(
+                    "the file of attachment id {} was removed when the trash got emptied, it can't be exported anymore",
+                    change.row_id
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,13 +1,55 @@
 use directories::ProjectDirs;
-use rusqlite::{Connection, NO_PARAMS};
+use rusqlite::{self, Connection, NO_PARAMS};
 use std::{
     convert::TryInto,
-    fs,
+    env,
+    ffi::OsStr,
+    fmt, fs,
     io::{self, Write},
     path, process,
 };
 
-const PROGRAM_NAME: &str = "rtend";
+use crate::config::{self, ConfigError};
+use crate::encryption;
+use crate::migration::{self, MigrationError};
+
+pub const PROGRAM_NAME: &str = "rtend";
+
+// Used until another profile is picked with `profile set-default`
+const DEFAULT_PROFILE: &str = "notes";
+
+// Point rtend at a database file, or at a directory holding the profiles, instead of the
+// platform's data dir
+pub const DB_ENV: &str = "RTEND_DB";
+pub const DATA_DIR_ENV: &str = "RTEND_DATA_DIR";
+
+// Errors of operations that touch both the database and files next to it
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "{}", e),
+            StoreError::Sqlite(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(e: rusqlite::Error) -> Self {
+        StoreError::Sqlite(e)
+    }
+}
 
 pub fn get_yn_input() -> Result<bool, ()> {
     let answer;
@@ -44,10 +86,6 @@ pub fn get_term_width() -> u16 {
     }
 }
 
-pub fn check_database_exists(name: &str) -> bool {
-    find_data_dir().unwrap().join(name).exists()
-}
-
 pub fn trim_trailing_newline(s: &mut String) -> String {
     while s.ends_with('\n') || s.ends_with('\r') {
         s.pop().unwrap();
@@ -56,91 +94,98 @@ pub fn trim_trailing_newline(s: &mut String) -> String {
     s.to_string()
 }
 
-pub fn check_first_time() -> bool {
-    let rtend_data_dir = find_data_dir().unwrap_or_else(|err| {
-        eprintln!("{}", err);
-        process::exit(1);
-    });
-    !rtend_data_dir.exists()
-}
-
 pub fn find_data_dir() -> Result<path::PathBuf, &'static str> {
-    if let Some(base_dir) = ProjectDirs::from("", "", PROGRAM_NAME) {
+    if let Some(data_dir) = env::var_os(DATA_DIR_ENV).filter(|dir| !dir.is_empty()) {
+        Ok(path::PathBuf::from(data_dir))
+    } else if let Some(base_dir) = ProjectDirs::from("", "", PROGRAM_NAME) {
         Ok(base_dir.data_dir().to_path_buf())
     } else {
         Err("Could not retrieve home directory. You maybe are using unsupported OS.")
     }
 }
 
-pub fn create_new_db(first_time: bool, name: &str) -> rusqlite::Result<()> {
-    let rtend_data_dir = find_data_dir().unwrap();
+pub fn get_db_path(conn: &Connection) -> rusqlite::Result<path::PathBuf> {
+    conn.query_row_and_then(
+        "SELECT file from pragma_database_list where name = 'main'",
+        NO_PARAMS,
+        |row| row.get::<_, String>(0).map(path::PathBuf::from),
+    )
+}
 
+pub fn get_default_profile() -> String {
+    config::get()
+        .default_profile
+        .clone()
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+pub fn set_default_profile(name: &str) -> Result<(), ConfigError> {
+    config::set("default_profile", Some(name))
+}
+
+// Profile names end up as file names in the data dir
+pub fn is_valid_profile_name(name: &str) -> bool {
+    !(name.is_empty() || name.starts_with('.') || name.contains(&['/', '\\'][..]))
+}
+
+// --db wins over --profile, which wins over RTEND_DB, and the default profile comes last
+pub fn find_db_path(db: Option<&OsStr>, profile: Option<&str>) -> path::PathBuf {
+    if let Some(db) = db {
+        return path::PathBuf::from(db);
+    }
+    if profile.is_none() {
+        if let Some(db) = env::var_os(DB_ENV).filter(|db| !db.is_empty()) {
+            return path::PathBuf::from(db);
+        }
+    }
+
+    let name = profile
+        .map(|name| name.to_string())
+        .unwrap_or_else(get_default_profile);
+    find_data_dir()
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+        .join(format!("{}.db", name))
+}
+
+pub fn open_db(
+    db_path: &path::Path,
+    passphrase: Option<&str>,
+) -> Result<Connection, MigrationError> {
+    let mut conn = Connection::open(db_path)?;
+    if let Some(passphrase) = passphrase {
+        encryption::apply_key(&conn, passphrase)?;
+    }
+
+    // Brings older databases up to the schema this binary expects
+    migration::migrate(&mut conn)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
+
+    Ok(conn)
+}
+
+pub fn create_new_db(db_path: &path::Path, passphrase: Option<&str>) -> Result<(), MigrationError> {
     println!(
         "rtend's data does not exist, will now create one at: {}",
-        rtend_data_dir.display()
+        db_path.display()
     );
 
-    if first_time {
-        match fs::create_dir(&rtend_data_dir) {
+    if let Some(data_dir) = db_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        match fs::create_dir_all(data_dir) {
             Ok(()) => (),
             Err(err) => eprintln!("Problem creating data directory: {}", err),
         }
     }
 
-    let conn = Connection::open(&rtend_data_dir.join(name))?;
+    let mut conn = Connection::open(db_path)?;
+    if let Some(passphrase) = passphrase {
+        encryption::apply_key(&conn, passphrase)?;
+    }
 
     // Importing schema
-    conn.execute(
-        "CREATE TABLE entity (
-             id integer primary key autoincrement,
-             created datetime not null default current_timestamp
-         )",
-        NO_PARAMS,
-    )?;
-
-    conn.execute(
-        "CREATE TABLE snippet (
-            id integer primary key,
-            entity_id integer references entity(id),
-            data text not null,
-            created datetime not null default current_timestamp,
-            updated datetime not null default current_timestamp
-         )",
-        NO_PARAMS,
-    )?;
-
-    conn.execute(
-        "CREATE TABLE alias (
-            id integer primary key,
-            entity_id integer references entity(id),
-            name varchar(255) not null,
-            created datetime not null default current_timestamp,
-            updated datetime not null default current_timestamp
-         )",
-        NO_PARAMS,
-    )?;
-
-    conn.execute(
-        "CREATE TABLE relation (
-            id integer primary key,
-            entity_id_a integer not null references entity(id),
-            entity_id_b integer not null references entity(id),
-            created datetime not null default current_timestamp,
-            updated datetime not null default current_timestamp
-         )",
-        NO_PARAMS,
-    )?;
-
-    conn.execute(
-        "CREATE TABLE relation_snippet (
-            id integer primary key,
-            relation_id integer not null references relation(id),
-            data text not null,
-            created datetime not null default current_timestamp,
-            updated datetime not null default current_timestamp
-         )",
-        NO_PARAMS,
-    )?;
+    migration::migrate(&mut conn)?;
 
     println!("Sucessully created database.");
     Ok(())